@@ -1,5 +1,6 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// A collection of handy command-line tools for developers.
 #[derive(Parser, Debug)]
@@ -12,21 +13,54 @@ pub struct Cli {
     /// Enables verbose output (debug logs).
     #[arg(long, global = true, action = clap::ArgAction::SetTrue)]
     pub verbose: bool,
+
+    /// Never let git prompt on the terminal, e.g. for a password or SSH key passphrase.
+    /// Repositories that need one are reported as "authentication required" instead.
+    #[arg(long, global = true, action = clap::ArgAction::SetTrue)]
+    pub non_interactive: bool,
 }
 
 /// An enum to hold all possible commands.
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    /// Collects repository content into a single JSON file.
+    /// Collects repository content into a single JSON, Markdown, XML or text file.
     Collect(CollectArgs),
     /// Checks the status of all git repositories in one or more directories.
-    CheckRepos(RemoteOperationArgs),
+    CheckRepos(CheckReposArgs),
     /// Pulls the latest's changes in the current branch for all git repositories
     /// in one or more directories.
-    PullRepos(RemoteOperationArgs),
+    PullRepos(PullReposArgs),
     /// Pushes the latest's changes in the current branch for all git repositores
     /// in one or more directories.
-    PushRepos(RemoteOperationArgs),
+    PushRepos(PushReposArgs),
+    /// Commits the local changes of all git repositories in one or more directories.
+    ///
+    /// Examples:
+    ///   fafnir commit-repos ~/projects -m "Update dependencies"
+    ///   fafnir commit-repos ~/projects -m "Rename docs" --all
+    CommitRepos(CommitReposArgs),
+    /// Stashes, restores and lists the local changes of all git repositories in one or more
+    /// directories.
+    StashRepos(StashReposArgs),
+    /// Switches, lists and cleans up branches in all git repositories in one or more directories.
+    Branch(BranchArgs),
+    /// Runs a command in every git repository in one or more directories.
+    ///
+    /// Examples:
+    ///   fafnir exec ~/projects -- cargo update
+    ///   fafnir exec ~/projects --only-clean -- git checkout main
+    #[command(alias = "foreach")]
+    Exec(ExecArgs),
+    /// Clones the repositories listed in a workspace manifest that are missing, then checks
+    /// (or pulls) all of them.
+    Sync(SyncArgs),
+    /// Reports how active all git repositories in one or more directories are: their last
+    /// commit, recent commits, branches and size on disk.
+    ///
+    /// Examples:
+    ///   fafnir repo-stats ~/projects --since 90d
+    ///   fafnir repo-stats ~/projects --export stats.csv
+    RepoStats(RepoStatsArgs),
 
     /// Renames multiple files (and explicitly-passed directories) in bulk.
     ///
@@ -39,14 +73,20 @@ pub enum Commands {
 
 /// Arguments specific to the 'collect' command.
 #[derive(Parser, Debug)]
+#[command(group(ArgGroup::new("changes").args(["changed_since", "staged", "diff"])))]
 pub struct CollectArgs {
     /// The root directory to start scanning from. Defaults to the CWD.
     #[arg(long, short = 'p', default_value = ".")]
     pub path: PathBuf,
 
-    /// The path to the output file. Defaults to `content.json`.
-    #[arg(long, short = 'o', default_value = "content.json")]
-    pub output_file: PathBuf,
+    /// The path to the output file. Defaults to `content.<ext>`, with the extension of the
+    /// format.
+    #[arg(long, short = 'o')]
+    pub output_file: Option<PathBuf>,
+
+    /// The format of the output file.
+    #[arg(long, short = 'f', value_enum, default_value_t = CollectFormat::Json)]
+    pub format: CollectFormat,
 
     /// Specific file or directory paths to ignore.
     #[arg(long, short = 'i', value_name = "PATH", num_args = 1..)]
@@ -55,14 +95,593 @@ pub struct CollectArgs {
     /// File or directory names to ignore globally, regardless of their path.
     #[arg(long, value_name = "FILENAME", num_args = 1..)]
     pub ignore_all: Vec<String>,
+
+    /// Only collect files matching these globs, e.g. `*.rs` or `src/**/*.ts`. With `--type`, files
+    /// must match both. Ignored files are never collected, whatever they match.
+    #[arg(long, value_name = "GLOB", num_args = 1..)]
+    pub include: Vec<String>,
+
+    /// Only collect files of these types, e.g. `rust,toml`. With `--include`, files must match
+    /// both. Types are those of ripgrep's `--type-list`.
+    #[arg(long = "type", short = 't', value_name = "TYPE", value_delimiter = ',')]
+    pub types: Vec<String>,
+
+    /// Leave out files of these types, e.g. `json,lock`.
+    #[arg(long = "exclude-type", short = 'T', value_name = "TYPE", value_delimiter = ',')]
+    pub exclude_types: Vec<String>,
+
+    /// Only collect the files changed since this revision, committed or not, and the untracked
+    /// ones, as they are in the working tree.
+    #[arg(long, value_name = "REV")]
+    pub changed_since: Option<String>,
+
+    /// Only collect the files with staged changes, as they are staged.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub staged: bool,
+
+    /// Only collect the files changed in this revision range, as they are at its end, e.g.
+    /// `main..feature`, or `main...feature` to compare with where the branches forked.
+    #[arg(long, value_name = "RANGE")]
+    pub diff: Option<String>,
+
+    /// What to write for each changed file: its content, the unified diff of its changes, or both.
+    #[arg(long, value_enum, default_value_t = Embed::Content, requires = "changes")]
+    pub embed: Embed,
+
+    /// Skip files larger than this, e.g. `500KiB` or `2MB`. `0` for no limit.
+    #[arg(long, value_name = "SIZE", default_value = "1MiB", value_parser = parse_size)]
+    pub max_file_size: u64,
+
+    /// How to read files that are not valid UTF-8.
+    #[arg(long, value_enum, default_value_t = TextEncoding::Utf8)]
+    pub encoding: TextEncoding,
+
+    /// The most tokens the collected contents may add up to. Files that don't fit are truncated
+    /// or left out, in the order set by `--priority`.
+    #[arg(long, value_name = "N")]
+    pub max_tokens: Option<usize>,
+
+    /// Truncate every file longer than this many tokens.
+    #[arg(long, value_name = "N")]
+    pub max_file_tokens: Option<usize>,
+
+    /// How to count tokens.
+    #[arg(long, value_enum, default_value_t = Tokenizer::Chars)]
+    pub tokenizer: Tokenizer,
+
+    /// Which files go in first when they don't all fit in `--max-tokens`.
+    #[arg(long, value_enum, default_value_t = Priority::SourceFirst, requires = "max_tokens")]
+    pub priority: Priority,
+
+    /// Print the token count of every file and directory once done.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub token_report: bool,
+}
+
+/// The formats `collect` can write.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollectFormat {
+    /// Nested objects mirroring the directory tree, with file contents as strings.
+    Json,
+    /// An array with the path, content and token count of each file.
+    JsonFlat,
+    /// A heading per file followed by its content in a fenced code block.
+    Markdown,
+    /// A `<file path="...">` element per file.
+    Xml,
+    /// The contents one after another, each under a `==> path <==` header.
+    Txt,
+}
+
+/// What `collect` writes for each file changed in git.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Embed {
+    /// The content after the change.
+    Content,
+    /// The unified diff of the change.
+    Diff,
+    /// The content after the change, followed by its diff.
+    Both,
+}
+
+/// How `collect` reads text that is not valid UTF-8.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// Skip files that are not valid UTF-8.
+    Utf8,
+    /// Read files as UTF-8, replacing invalid bytes with `�`.
+    Lossy,
+    /// Read UTF-16 files by their byte order mark or byte pattern, and files that are not valid
+    /// UTF-8 as Latin-1 (Windows-1252).
+    Detect,
+}
+
+/// The ways `collect` can count tokens.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// Estimate one token per four characters.
+    Chars,
+    /// The BPE encoding of GPT-4 and GPT-3.5.
+    #[cfg(feature = "tiktoken")]
+    Cl100k,
+    /// The BPE encoding of GPT-4o and later models.
+    #[cfg(feature = "tiktoken")]
+    O200k,
+}
+
+/// The order in which `collect` fits files into `--max-tokens`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    /// Source files before tests, and smaller files first within each.
+    SourceFirst,
+    /// Smaller files first, to fit as many files as possible.
+    Smallest,
+    /// In path order.
+    Path,
 }
 
 /// Arguments specific to the `check-repos`, `pull-repos`, and `push-repos` commands.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 pub struct RemoteOperationArgs {
     /// The parent directories to run the remote operations.
     #[arg(required = true, num_args = 1..)]
     pub directories: Vec<PathBuf>,
+
+    /// How many directory levels below each parent directory to search for repositories.
+    /// The search stops descending as soon as a repository is found.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub max_depth: usize,
+
+    /// Follow symbolic links while searching for repositories.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub follow_symlinks: bool,
+
+    #[command(flatten)]
+    pub filters: RepoFilters,
+
+    /// The number of repositories to check and act on concurrently.
+    #[arg(long, short = 'j', value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Allow pulling into and pushing from repositories whose only changes are untracked files.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub allow_untracked: bool,
+
+    /// Fetch from the remote before checking each repository, so "behind" counts are accurate.
+    /// Not allowed with `--dry-run`, which changes nothing, remote-tracking refs included.
+    #[arg(long, conflicts_with = "dry_run", action = clap::ArgAction::SetTrue)]
+    pub fetch: bool,
+
+    /// Remove remote-tracking references that no longer exist on the remote while fetching.
+    #[arg(long, requires = "fetch", action = clap::ArgAction::SetTrue)]
+    pub prune: bool,
+
+    /// How long to wait for `git fetch` in each repository before giving up, in seconds.
+    #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+    pub fetch_timeout: u64,
+
+    /// How long to wait for each pull or push before killing git, in seconds.
+    #[arg(long, value_name = "SECONDS", default_value_t = 600)]
+    pub timeout: u64,
+
+    /// How to talk to Git. `libgit2` checks status in-process, without spawning `git`.
+    #[arg(long, value_enum, default_value_t = GitBackendKind::Cli)]
+    pub backend: GitBackendKind,
+
+    /// Show what `pull-repos`/`push-repos` would run, and which commits they would move,
+    /// without changing any repository.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub dry_run: bool,
+
+    /// The format of the final report.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+impl Default for RemoteOperationArgs {
+    /// The arguments with no option given and no directory, for commands that run the remote
+    /// operations on repositories they find themselves.
+    fn default() -> Self {
+        Self {
+            directories: Vec::new(),
+            ..Self::parse_from(["fafnir", "."])
+        }
+    }
+}
+
+/// Narrow down the repositories a batch Git command acts on. A repository must pass every
+/// filter that is given; filters that can be given more than once accept any of their values.
+#[derive(Args, Debug, Clone, Default)]
+pub struct RepoFilters {
+    /// Only include repositories whose path matches this glob, e.g. `acme/*`. Matched against
+    /// the path relative to the searched directory, and against the full path.
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip repositories whose path matches this glob.
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Only include repositories that currently have this branch checked out.
+    #[arg(long, value_name = "NAME")]
+    pub branch: Option<String>,
+
+    /// Only include repositories with a remote whose URL matches this glob, e.g. `*gitlab.com*acme/*`.
+    #[arg(long, value_name = "GLOB")]
+    pub remote_url: Option<String>,
+
+    /// Only report and act on repositories in this state.
+    #[arg(long, value_enum, value_name = "STATE")]
+    pub status: Vec<StatusFilter>,
+
+    /// Only include the repositories of this group of the workspace manifest.
+    #[arg(long = "group", value_name = "NAME")]
+    pub groups: Vec<String>,
+
+    /// The workspace manifest that defines the groups.
+    #[arg(long, value_name = "FILE", default_value = "fafnir.toml")]
+    pub manifest: PathBuf,
+}
+
+/// States that `--status` can select repositories by.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusFilter {
+    /// Staged, modified, untracked or conflicted files.
+    Dirty,
+    /// Commits not pushed to the upstream yet.
+    Ahead,
+    /// Commits on the upstream not pulled yet.
+    Behind,
+}
+
+/// Arguments for `check-repos`.
+#[derive(Parser, Debug)]
+pub struct CheckReposArgs {
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// Open an interactive dashboard instead of printing the report.
+    #[cfg(feature = "tui")]
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub tui: bool,
+
+    /// Keep checking the repositories, printing the report again and what changed since the
+    /// previous check, until interrupted.
+    #[cfg_attr(feature = "tui", arg(conflicts_with = "tui"))]
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub watch: bool,
+
+    /// How long to wait between two checks in watch mode, e.g. `30s`, `5m`.
+    #[arg(long, value_name = "DURATION", default_value = "60s", value_parser = humantime::parse_duration, requires = "watch")]
+    pub interval: Duration,
+
+    /// In watch mode, also check again as soon as something changes in a `.git` directory,
+    /// e.g. after a commit, checkout or fetch.
+    #[arg(long, requires = "watch", action = clap::ArgAction::SetTrue)]
+    pub on_change: bool,
+}
+
+/// Arguments for `pull-repos`.
+#[derive(Parser, Debug)]
+pub struct PullReposArgs {
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// How to integrate the upstream changes, regardless of the `pull.rebase` git setting.
+    #[arg(long, value_enum, default_value_t = PullStrategy::FfOnly)]
+    pub strategy: PullStrategy,
+
+    /// Stash local changes before pulling and restore them afterwards.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub autostash: bool,
+
+    /// After a successful pull, initialize and update the submodules to the commits the pulled
+    /// branch records, recursively.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub update_submodules: bool,
+}
+
+/// Arguments for `push-repos`.
+#[derive(Parser, Debug)]
+pub struct PushReposArgs {
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// Push branches without an upstream too, and set the pushed branch as their upstream.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub set_upstream: bool,
+
+    /// Push tags along with the branch, even when the branch itself is up to date.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub tags: bool,
+
+    /// Overwrite the remote branch if it hasn't moved since the last fetch, e.g. after a rebase.
+    /// Asks for confirmation first.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub force_with_lease: bool,
+
+    /// The remote to push to, instead of the branch's own remote.
+    #[arg(long = "remote", value_name = "NAME")]
+    pub remote_name: Option<String>,
+
+    /// Don't ask for confirmation before force-pushing.
+    #[arg(long, short = 'y', action = clap::ArgAction::SetTrue)]
+    pub yes: bool,
+}
+
+/// Arguments for `commit-repos`.
+#[derive(Parser, Debug)]
+pub struct CommitReposArgs {
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// The commit message.
+    #[arg(long, short = 'm', required = true)]
+    pub message: String,
+
+    /// Commit every change, including untracked files, instead of only the staged ones.
+    #[arg(long, conflicts_with = "paths", action = clap::ArgAction::SetTrue)]
+    pub all: bool,
+
+    /// Commit every change, including untracked files, to these paths only.
+    #[arg(long, value_name = "PATHSPEC", num_args = 1..)]
+    pub paths: Vec<String>,
+
+    /// Don't ask for confirmation before committing.
+    #[arg(long, short = 'y', action = clap::ArgAction::SetTrue)]
+    pub yes: bool,
+}
+
+/// Arguments for `stash-repos`.
+#[derive(Parser, Debug)]
+pub struct StashReposArgs {
+    #[command(subcommand)]
+    pub command: StashCommand,
+}
+
+/// The `stash-repos` subcommands.
+#[derive(Subcommand, Debug)]
+pub enum StashCommand {
+    /// Stashes the local changes of every repository that has some.
+    Push(StashPushArgs),
+    /// Restores the latest stash entry of every repository that has one.
+    Pop(StashPopArgs),
+    /// Shows the stash entries of every repository.
+    List(RemoteOperationArgs),
+}
+
+/// Arguments for `stash-repos push`.
+#[derive(Parser, Debug)]
+pub struct StashPushArgs {
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// The description of the stash entry.
+    #[arg(long, short = 'm')]
+    pub message: Option<String>,
+
+    /// Stash untracked files too.
+    #[arg(long, short = 'u', action = clap::ArgAction::SetTrue)]
+    pub include_untracked: bool,
+
+    /// Don't ask for confirmation before stashing.
+    #[arg(long, short = 'y', action = clap::ArgAction::SetTrue)]
+    pub yes: bool,
+}
+
+/// Arguments for `stash-repos pop`.
+#[derive(Parser, Debug)]
+pub struct StashPopArgs {
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// Don't ask for confirmation before restoring.
+    #[arg(long, short = 'y', action = clap::ArgAction::SetTrue)]
+    pub yes: bool,
+}
+
+/// Arguments for `branch`.
+#[derive(Parser, Debug)]
+pub struct BranchArgs {
+    #[command(subcommand)]
+    pub command: BranchCommand,
+}
+
+/// The `branch` subcommands.
+#[derive(Subcommand, Debug)]
+pub enum BranchCommand {
+    /// Switches every clean repository to the same branch.
+    ///
+    /// Examples:
+    ///   fafnir branch switch main ~/projects
+    ///   fafnir branch switch migrate-to-v2 ~/projects --create
+    Switch(BranchSwitchArgs),
+    /// Shows the current branch of every repository in a table.
+    List(RemoteOperationArgs),
+    /// Deletes the local branches that were merged into the default branch.
+    DeleteMerged(DeleteMergedArgs),
+    /// Deletes the local branches whose upstream branch was deleted from the remote.
+    PruneGone(PruneGoneArgs),
+}
+
+/// Arguments for `branch switch`.
+#[derive(Parser, Debug)]
+pub struct BranchSwitchArgs {
+    /// The branch to switch to.
+    pub name: String,
+
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// Create the branch from the current commit in repositories that don't have it yet.
+    #[arg(long, short = 'c', action = clap::ArgAction::SetTrue)]
+    pub create: bool,
+}
+
+/// Arguments for `branch delete-merged`.
+#[derive(Parser, Debug)]
+pub struct DeleteMergedArgs {
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// The remote whose `HEAD` is the default branch, e.g. `upstream` in a fork.
+    #[arg(long = "remote", value_name = "NAME", default_value = "origin")]
+    pub remote_name: String,
+}
+
+/// Arguments for `branch prune-gone`.
+#[derive(Parser, Debug)]
+pub struct PruneGoneArgs {
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// Also delete branches with commits that were never merged, e.g. after a squash merge.
+    /// Asks for confirmation first.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub force: bool,
+
+    /// Don't ask for confirmation before force-deleting branches.
+    #[arg(long, short = 'y', action = clap::ArgAction::SetTrue)]
+    pub yes: bool,
+}
+
+/// Arguments for `exec`.
+#[derive(Parser, Debug)]
+pub struct ExecArgs {
+    #[command(flatten)]
+    pub remote: RemoteOperationArgs,
+
+    /// Only run the command in repositories with a clean working tree and no merge, rebase or
+    /// similar operation in progress. Untracked files are fine with `--allow-untracked`.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub only_clean: bool,
+
+    /// The command to run in each repository, after `--`.
+    #[arg(last = true, required = true, value_name = "COMMAND")]
+    pub command: Vec<String>,
+}
+
+/// Arguments for `sync`.
+#[derive(Parser, Debug)]
+pub struct SyncArgs {
+    /// The manifest listing the repositories of the workspace, in TOML or YAML.
+    #[arg(long, short = 'm', value_name = "FILE", default_value = "fafnir.toml")]
+    pub manifest: PathBuf,
+
+    /// Only sync the repositories in this group. Can be repeated.
+    #[arg(long = "group", value_name = "NAME")]
+    pub groups: Vec<String>,
+
+    /// Pull the repositories that were already cloned, instead of only checking them.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub pull: bool,
+
+    /// How to integrate the upstream changes when pulling.
+    #[arg(long, value_enum, default_value_t = PullStrategy::FfOnly, requires = "pull")]
+    pub strategy: PullStrategy,
+
+    /// How long to wait for each clone or pull before killing git, in seconds.
+    #[arg(long, value_name = "SECONDS", default_value_t = 600)]
+    pub timeout: u64,
+
+    /// The number of repositories to clone, check and pull concurrently.
+    #[arg(long, short = 'j', value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Fetch from the remote before checking each repository, so "behind" counts are accurate.
+    /// Not allowed with `--dry-run`, which changes nothing, remote-tracking refs included.
+    #[arg(long, conflicts_with = "dry_run", action = clap::ArgAction::SetTrue)]
+    pub fetch: bool,
+
+    /// How to talk to Git. `libgit2` checks status in-process, without spawning `git`.
+    #[arg(long, value_enum, default_value_t = GitBackendKind::Cli)]
+    pub backend: GitBackendKind,
+
+    /// Show which repositories would be cloned and what would be pulled, without changing anything.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub dry_run: bool,
+
+    /// The format of the final report.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+}
+
+/// Arguments for `repo-stats`. Only reads the repositories, so it takes the discovery and report
+/// options of the batch Git commands, but none of those that act on them.
+#[derive(Parser, Debug)]
+pub struct RepoStatsArgs {
+    /// The parent directories to search for repositories.
+    #[arg(required = true, num_args = 1..)]
+    pub directories: Vec<PathBuf>,
+
+    /// How many directory levels below each parent directory to search for repositories.
+    /// The search stops descending as soon as a repository is found.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub max_depth: usize,
+
+    /// Follow symbolic links while searching for repositories.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    pub follow_symlinks: bool,
+
+    #[command(flatten)]
+    pub filters: RepoFilters,
+
+    /// The number of repositories to read concurrently.
+    #[arg(long, short = 'j', value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+
+    /// How to talk to Git. `libgit2` checks status in-process, without spawning `git`.
+    #[arg(long, value_enum, default_value_t = GitBackendKind::Cli)]
+    pub backend: GitBackendKind,
+
+    /// The format of the report.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+
+    /// The window recent commits are counted in, going back from now, e.g. `30d`, `12weeks`.
+    #[arg(long, value_name = "DURATION", default_value = "30d", value_parser = humantime::parse_duration)]
+    pub since: Duration,
+
+    /// Also write the statistics to this file, as CSV or JSON depending on its extension.
+    #[arg(long, value_name = "FILE")]
+    pub export: Option<PathBuf>,
+
+    /// The remote whose `HEAD` is the default branch, which unmerged branches are measured against.
+    #[arg(long = "remote", value_name = "NAME", default_value = "origin")]
+    pub remote_name: String,
+}
+
+/// The ways `pull-repos` can integrate upstream changes.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PullStrategy {
+    /// Only update branches that can be fast-forwarded.
+    FfOnly,
+    /// Rebase local commits on top of the upstream branch.
+    Rebase,
+    /// Merge the upstream branch, creating a merge commit if needed.
+    Merge,
+}
+
+/// The available implementations of the Git operations.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GitBackendKind {
+    /// Run the `git` executable.
+    Cli,
+    /// Use the in-process libgit2 library. Fetching, pulling and pushing still run `git`,
+    /// so credential helpers and SSH configuration keep working.
+    #[cfg(feature = "libgit2")]
+    Libgit2,
+}
+
+/// Output formats for the report of the batch Git commands.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Human-readable, colored text.
+    Text,
+    /// A single JSON document with every repository and a summary.
+    Json,
+    /// One JSON object per line: a record per repository, followed by the summary.
+    Ndjson,
 }
 
 /// Arguments for `bulk-rename`.
@@ -82,3 +701,23 @@ pub struct BulkRenameArgs {
     #[arg(short = 'r', long = "recursive", action = clap::ArgAction::SetTrue)]
     pub recursive: bool,
 }
+
+/// Parses a size in bytes with an optional unit, e.g. `1500`, `500k`, `2MB` or `1.5GiB`. Decimal
+/// and binary units are both taken as powers of 1024.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number.parse().map_err(|_| format!("invalid size '{}'", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => return Err(format!("unknown unit in '{}', use B, KiB, MiB or GiB", value)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
@@ -167,7 +167,7 @@ fn infer_search_root(pattern: &str) -> Result<PathBuf> {
     if let Some(mi) = first_meta_idx {
         // find last separator before meta
         let before = &pattern[..mi];
-        let sep_idx = before.rmatch_indices(|c: char| c == '/' || c == '\\').next().map(|x| x.0);
+        let sep_idx = before.rmatch_indices(['/', '\\']).next().map(|x| x.0);
         let root = if let Some(si) = sep_idx {
             &before[..=si] // include the separator
         } else {
@@ -0,0 +1,775 @@
+use crate::cli::PullStrategy;
+use crate::commands::git_utilities::backend::error::{FailureKind, GitFailure, LeftConflicted, NeedsIntervention};
+use crate::commands::git_utilities::backend::{Activity, ChangeSelection, CommitInfo, GitBackend, PullOptions, PushOptions};
+use crate::commands::git_utilities::process::{self, output_with_timeout};
+use crate::commands::git_utilities::status::{parse_porcelain_v2, parse_submodule_status, Operation, RepoStatus};
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::Duration;
+
+/// The arguments `CliBackend::update_submodules` passes to git.
+pub const SUBMODULE_UPDATE_ARGS: [&str; 4] = ["submodule", "update", "--init", "--recursive"];
+
+/// Runs the `git` executable found in `PATH` for every operation.
+pub struct CliBackend;
+
+impl GitBackend for CliBackend {
+    fn status(&self, path: &Path) -> Result<Option<RepoStatus>> {
+        // 1. Check if it's a git repository, and find its git directory
+        let rev_parse_output = Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .arg("rev-parse")
+            .arg("--is-inside-work-tree")
+            .arg("--absolute-git-dir")
+            .output()?;
+
+        if !rev_parse_output.status.success() {
+            return Ok(None);
+        }
+
+        let rev_parse = String::from_utf8_lossy(&rev_parse_output.stdout);
+        let mut lines = rev_parse.lines();
+        if lines.next() != Some("true") {
+            return Ok(None);
+        }
+        let git_dir = lines.next().map(PathBuf::from);
+
+        // 2. Read the branch, upstream, ahead/behind counts and changed files in one go
+        let status_output = Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .arg("status")
+            .arg("--porcelain=v2")
+            .arg("--branch")
+            .arg("-z")
+            .output()?;
+        if !status_output.status.success() {
+            bail!(
+                "git status failed: {}",
+                String::from_utf8_lossy(&status_output.stderr).trim()
+            );
+        }
+
+        let mut status = parse_porcelain_v2(&String::from_utf8_lossy(&status_output.stdout));
+
+        // 3. Count the stash entries
+        let stash_output = Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .arg("stash")
+            .arg("list")
+            .output()?;
+        status.stashes = String::from_utf8_lossy(&stash_output.stdout).lines().count();
+
+        // 4. Look for an interrupted merge, rebase, etc.
+        status.operation = git_dir.as_deref().and_then(operation_in_progress);
+
+        // 5. Find the submodules that were never initialized, and nested ones out of sync, which
+        //    `git status` doesn't show
+        if path.join(".gitmodules").is_file() {
+            // Not trimmed like `git_output` does: the first column of every line is significant
+            let submodule_output = Command::new("git")
+                .arg("-C")
+                .arg(path)
+                .args(["submodule", "status", "--recursive"])
+                .output()?;
+            parse_submodule_status(&String::from_utf8_lossy(&submodule_output.stdout), &mut status);
+        }
+
+        // 6. For a branch without upstream, find remote branches that could become one
+        if let (Some(branch), None) = (&status.branch, status.ahead) {
+            let refs_output = Command::new("git")
+                .arg("-C")
+                .arg(path)
+                .arg("for-each-ref")
+                .arg("--format=%(refname:short)")
+                .arg(format!("refs/remotes/*/{}", branch))
+                .output()?;
+            status.matching_remote_branches = String::from_utf8_lossy(&refs_output.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect();
+        }
+
+        Ok(Some(status))
+    }
+
+    fn fetch(&self, path: &Path, prune: bool, timeout: Duration) -> Result<()> {
+        let mut command = remote_git(path);
+        command.arg("fetch").arg("--quiet");
+        if prune {
+            command.arg("--prune");
+        }
+
+        let output = output_with_timeout(&mut command, timeout)?;
+        if !output.status.success() {
+            return Err(GitFailure::new("fetch", &output.stderr).into());
+        }
+
+        Ok(())
+    }
+
+    fn pull(&self, path: &Path, options: PullOptions) -> Result<()> {
+        let args = pull_args(options);
+        let output = run_remote(remote_git(path).args(&args), options.timeout);
+        if output.as_ref().is_ok_and(|output| output.status.success()) {
+            // Git reports success even when re-applying the autostashed changes stops on conflicts
+            if options.autostash && self.status(path)?.is_some_and(|status| !status.conflicted.is_empty()) {
+                return Err(LeftConflicted {
+                    reason: "pulled, but re-applying the local changes stopped with conflicts, they are kept in the stash"
+                        .to_string(),
+                }
+                .into());
+            }
+            return Ok(());
+        }
+
+        // A rebase or merge that stopped on conflicts, or was killed for taking too long, leaves
+        // the repository half-way: roll it back so the repository is left exactly as it was
+        // before the pull
+        let status = self.status(path)?;
+        if let Some(operation) = status.as_ref().and_then(|status| status.operation) {
+            let abort = match operation {
+                Operation::Rebase => Some("rebase"),
+                Operation::Merge => Some("merge"),
+                _ => None,
+            };
+            if let Some(command) = abort {
+                run_git(path, &[command, "--abort"])?;
+                let cause = if output.is_ok() { "stopped with conflicts" } else { "timed out" };
+                return Err(NeedsIntervention {
+                    reason: format!("{} {} and was aborted", operation.as_str(), cause),
+                }
+                .into());
+            }
+        }
+
+        // The pull fetched before failing, so the ahead/behind counts are up to date
+        let mut failure = GitFailure::new("pull", &output?.stderr);
+        if options.strategy == PullStrategy::FfOnly && status.is_some_and(|status| status.is_diverged()) {
+            failure.kind = FailureKind::Diverged;
+        }
+        Err(failure.into())
+    }
+
+    fn push(&self, path: &Path, options: &PushOptions) -> Result<()> {
+        let args = push_args(path, options)?;
+        let output = run_remote(remote_git(path).args(&args), options.timeout)?;
+        if !output.status.success() {
+            return Err(GitFailure::new("push", &output.stderr).into());
+        }
+
+        Ok(())
+    }
+
+    fn update_submodules(&self, path: &Path, timeout: Duration) -> Result<()> {
+        let mut command = remote_git(path);
+        command.args(SUBMODULE_UPDATE_ARGS);
+        let output = output_with_timeout(&mut command, timeout)?;
+        if !output.status.success() {
+            return Err(GitFailure::new("submodule update", &output.stderr).into());
+        }
+
+        Ok(())
+    }
+
+    fn clone_repo(&self, url: &str, path: &Path, branch: Option<&str>, timeout: Duration) -> Result<()> {
+        let mut command = remote_git_outside_repository();
+        command.arg("clone");
+        if let Some(branch) = branch {
+            command.arg("--branch").arg(branch);
+        }
+        let existed = path.exists();
+        let output = output_with_timeout(command.arg("--").arg(url).arg(path), timeout).inspect_err(|_| {
+            // A killed clone leaves a half-written repository behind, which would not be cloned again
+            if !existed {
+                let _ = std::fs::remove_dir_all(path);
+            }
+        })?;
+
+        if !output.status.success() {
+            return Err(GitFailure::new("clone", &output.stderr).into());
+        }
+
+        Ok(())
+    }
+
+    fn switch_branch(&self, path: &Path, name: &str, create: bool) -> Result<()> {
+        let exists = git_output(path, &["rev-parse", "--verify", "--quiet", &format!("refs/heads/{}", name)])?.is_some();
+        if create && !exists {
+            run_git(path, &["switch", "--create", name])
+        } else {
+            run_git(path, &["switch", name])
+        }
+    }
+
+    fn merged_branches(&self, path: &Path, remote: &str) -> Result<Vec<String>> {
+        let default_branch = default_branch(path, remote)?;
+        let output = git_output(path, &["branch", "--merged", &default_branch, "--format=%(HEAD)|%(refname:short)"])?
+            .unwrap_or_default();
+
+        // The default branch may be the local counterpart of the remote's HEAD, e.g. `main` for `origin/main`
+        let local_default = default_branch.split_once('/').map_or(default_branch.as_str(), |(_, name)| name);
+        Ok(not_checked_out(&output)
+            .filter(|branch| *branch != default_branch && *branch != local_default)
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn gone_branches(&self, path: &Path) -> Result<Vec<String>> {
+        let output = git_output(
+            path,
+            &["for-each-ref", "--format=%(HEAD)|%(refname:short) %(upstream:track)", "refs/heads"],
+        )?
+        .unwrap_or_default();
+
+        Ok(not_checked_out(&output)
+            .filter_map(|line| line.strip_suffix(" [gone]"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn delete_branch(&self, path: &Path, name: &str, force: bool) -> Result<()> {
+        run_git(path, &["branch", if force { "-D" } else { "-d" }, name])
+    }
+
+    fn commit(&self, path: &Path, message: &str, selection: &ChangeSelection) -> Result<()> {
+        let paths: Vec<&str> = selection.paths.iter().map(String::as_str).collect();
+
+        if !selection.staged_only {
+            let stage = if selection.untracked { "--all" } else { "--update" };
+            run_git(path, &[&["add", stage, "--"], paths.as_slice()].concat())?;
+        }
+
+        // With pathspecs, only those paths are committed, even if other changes were staged before
+        run_git(path, &[&["commit", "--message", message, "--"], paths.as_slice()].concat())
+    }
+
+    fn has_changes(&self, path: &Path, selection: &ChangeSelection) -> Result<bool> {
+        let untracked = if selection.untracked && !selection.staged_only {
+            "--untracked-files=all"
+        } else {
+            "--untracked-files=no"
+        };
+        let paths: &[String] = if selection.staged_only { &[] } else { &selection.paths };
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .args(["status", "--porcelain", untracked, "--"])
+            .args(paths)
+            .output()?;
+        if !output.status.success() {
+            return Err(GitFailure::new("status", &output.stderr).into());
+        }
+
+        // The first column is the state of the index, blank for changes that are not staged
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .any(|line| !selection.staged_only || !matches!(line.chars().next(), Some(' ' | '?'))))
+    }
+
+    fn diffstat(&self, path: &Path, selection: &ChangeSelection) -> Result<Vec<String>> {
+        let paths: Vec<&str> = selection.paths.iter().map(String::as_str).collect();
+        // Before the first commit, only the index has changes to compare
+        let base = if selection.staged_only || !has_head(path) { "--cached" } else { "HEAD" };
+
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .args(["diff", "--stat", base, "--"])
+            .args(&paths)
+            .output()?;
+        if !output.status.success() {
+            return Err(GitFailure::new("diff", &output.stderr).into());
+        }
+        let mut lines: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect();
+
+        if selection.untracked && !selection.staged_only {
+            let untracked = git_output(path, &[&["ls-files", "--others", "--exclude-standard", "--"], paths.as_slice()].concat())?
+                .unwrap_or_default();
+            lines.extend(untracked.lines().map(|file| format!("{} (untracked)", file)));
+        }
+
+        Ok(lines)
+    }
+
+    fn stash_push(&self, path: &Path, message: Option<&str>, include_untracked: bool) -> Result<()> {
+        let mut args = vec!["stash", "push"];
+        if include_untracked {
+            args.push("--include-untracked");
+        }
+        if let Some(message) = message {
+            args.extend(["--message", message]);
+        }
+        run_git(path, &args)
+    }
+
+    fn stash_pop(&self, path: &Path) -> Result<()> {
+        let result = run_git(path, &["stash", "pop"]);
+
+        // On conflicts git keeps the entry and leaves conflict markers in the working tree
+        if result.is_err() && self.status(path)?.is_some_and(|status| !status.conflicted.is_empty()) {
+            return Err(LeftConflicted {
+                reason: "stash pop stopped with conflicts, the stash entry was kept".to_string(),
+            }
+            .into());
+        }
+
+        result
+    }
+
+    fn stash_list(&self, path: &Path) -> Result<Vec<String>> {
+        let output = git_output(path, &["stash", "list"])?.unwrap_or_default();
+        Ok(output.lines().map(str::to_string).collect())
+    }
+
+    fn last_commit(&self, path: &Path) -> Result<Option<CommitInfo>> {
+        // Fails on a branch without commits yet, which has no last commit
+        let Some(output) = git_output(path, &["log", "-1", "--format=%ct%x00%an"])? else {
+            return Ok(None);
+        };
+
+        Ok(output.split_once('\0').and_then(|(timestamp, author)| {
+            Some(CommitInfo {
+                timestamp: timestamp.parse().ok()?,
+                author: author.to_string(),
+            })
+        }))
+    }
+
+    fn activity(&self, path: &Path, since: i64, remote: &str) -> Result<Activity> {
+        // Fails on a branch without commits yet, which has no recent commits either
+        let recent_commits = git_output(path, &["rev-list", "--count", &format!("--since=@{}", since), "HEAD"])?
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(0);
+        let count_refs = |pattern: &str| -> Result<usize> {
+            let output = git_output(path, &["for-each-ref", "--format=%(refname)", pattern])?.unwrap_or_default();
+            Ok(output.lines().filter(|name| !name.ends_with("/HEAD")).count())
+        };
+
+        // Without a default branch there is nothing to be unmerged into
+        let oldest_unmerged = match default_branch(path, remote) {
+            Ok(default_branch) => git_output(
+                path,
+                &[
+                    "for-each-ref",
+                    &format!("--no-merged={}", default_branch),
+                    "--sort=committerdate",
+                    "--count=1",
+                    "--format=%(committerdate:unix)|%(refname:short)",
+                    "refs/heads",
+                ],
+            )?
+            .and_then(|line| {
+                let (timestamp, name) = line.split_once('|')?;
+                Some((name.to_string(), timestamp.parse().ok()?))
+            }),
+            Err(_) => None,
+        };
+
+        Ok(Activity {
+            recent_commits,
+            local_branches: count_refs("refs/heads")?,
+            remote_branches: count_refs("refs/remotes")?,
+            oldest_unmerged,
+        })
+    }
+
+    fn commits(&self, path: &Path, include: &str, exclude: &[&str]) -> Result<Vec<String>> {
+        let mut command = Command::new("git");
+        command
+            .arg("-C")
+            .arg(path)
+            .arg("log")
+            .arg("--format=%h %s")
+            .arg(include)
+            .arg("--not");
+        for revision in exclude {
+            if revision.contains('*') {
+                command.arg(format!("--glob={}", revision));
+            } else {
+                command.arg(revision);
+            }
+        }
+
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(GitFailure::new("log", &output.stderr).into());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// The arguments `CliBackend::pull` passes to git for the given options.
+pub fn pull_args(options: PullOptions) -> Vec<&'static str> {
+    let mut args = vec![
+        "pull",
+        match options.strategy {
+            PullStrategy::FfOnly => "--ff-only",
+            PullStrategy::Rebase => "--rebase",
+            PullStrategy::Merge => "--no-rebase",
+        },
+    ];
+    if options.autostash {
+        args.push("--autostash");
+    }
+    args
+}
+
+/// The arguments `CliBackend::push` passes to git for the given options.
+pub fn push_args(path: &Path, options: &PushOptions) -> Result<Vec<String>> {
+    let mut args = vec!["push".to_string()];
+    if options.force_with_lease {
+        args.push("--force-with-lease".to_string());
+    }
+    if options.set_upstream {
+        args.push("--set-upstream".to_string());
+    }
+    if options.tags {
+        args.push("--tags".to_string());
+    }
+
+    // Name the remote and branch explicitly when choosing a remote, creating an upstream, or
+    // pushing tags (which would otherwise push only the tags)
+    if options.remote.is_some() || options.set_upstream || options.tags {
+        let remote = match &options.remote {
+            Some(remote) => remote.clone(),
+            None => default_remote(path)?,
+        };
+        args.push(remote);
+        args.push("HEAD".to_string());
+    }
+
+    Ok(args)
+}
+
+/// Finds the remote `git push` would use: the branch's remote, `remote.pushDefault`, `origin`,
+/// or the repository's only remote.
+/// Whether the repository at `path` has a commit checked out. Before its first commit, `HEAD`
+/// names a branch that doesn't exist yet.
+pub fn has_head(path: &Path) -> bool {
+    git_output(path, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok_and(|output| output.is_some())
+}
+
+fn default_remote(path: &Path) -> Result<String> {
+    if let Some(branch) = git_output(path, &["symbolic-ref", "--short", "HEAD"])? {
+        if let Some(remote) = git_output(path, &["config", &format!("branch.{}.remote", branch)])? {
+            return Ok(remote);
+        }
+    }
+
+    if let Some(remote) = git_output(path, &["config", "remote.pushDefault"])? {
+        return Ok(remote);
+    }
+
+    let remotes = git_output(path, &["remote"])?.unwrap_or_default();
+    let remotes: Vec<&str> = remotes.lines().collect();
+    match remotes.as_slice() {
+        _ if remotes.contains(&"origin") => Ok("origin".to_string()),
+        [remote] => Ok(remote.to_string()),
+        [] => bail!("The repository has no remote to push to"),
+        _ => bail!("Cannot tell which remote to push to, pass --remote"),
+    }
+}
+
+/// Parses `%(HEAD)|<rest>` lines, returning `<rest>` for every branch but the checked out one.
+fn not_checked_out(output: &str) -> impl Iterator<Item = &str> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('|'))
+        .filter(|(head, _)| head.trim() != "*")
+        .map(|(_, rest)| rest)
+}
+
+/// Finds the branch new work is merged into: the `HEAD` of `remote` (e.g. `origin/main`), or a
+/// local `main` or `master`.
+fn default_branch(path: &Path, remote: &str) -> Result<String> {
+    let remote_head = format!("refs/remotes/{}/HEAD", remote);
+    if let Some(remote_head) = git_output(path, &["symbolic-ref", "--quiet", "--short", &remote_head])? {
+        return Ok(remote_head);
+    }
+
+    for candidate in ["main", "master"] {
+        let reference = format!("refs/heads/{}", candidate);
+        if git_output(path, &["rev-parse", "--verify", "--quiet", &reference])?.is_some() {
+            return Ok(candidate.to_string());
+        }
+    }
+
+    bail!("Cannot tell the default branch, run: git remote set-head {} --auto", remote)
+}
+
+/// Starts a `git` command in `path` that may talk to a remote. In non-interactive mode, git
+/// and ssh fail right away instead of prompting for credentials or host keys.
+fn remote_git(path: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(path);
+    prevent_prompts(&mut command, Some(path));
+    command
+}
+
+/// Like `remote_git`, for a command that runs outside any repository, e.g. `git clone`.
+fn remote_git_outside_repository() -> Command {
+    let mut command = Command::new("git");
+    prevent_prompts(&mut command, None);
+    command
+}
+
+fn prevent_prompts(command: &mut Command, repository: Option<&Path>) {
+    if process::is_non_interactive() {
+        command.env("GIT_TERMINAL_PROMPT", "0").env("GCM_INTERACTIVE", "never");
+        if let Some(ssh) = ssh_command(repository) {
+            command.env("GIT_SSH_COMMAND", format!("{} -o BatchMode=yes", ssh));
+        }
+    }
+}
+
+/// The ssh command git would use in `repository`, so `BatchMode` can be added to it without
+/// losing the user's own options. Outside a repository only the global config applies, not that
+/// of whatever repository the current directory is in.
+fn ssh_command(repository: Option<&Path>) -> Option<String> {
+    if let Ok(command) = std::env::var("GIT_SSH_COMMAND") {
+        return Some(command);
+    }
+    // `GIT_SSH` names a program that may not accept ssh options, leave it alone
+    if std::env::var_os("GIT_SSH").is_some() {
+        return None;
+    }
+    let configured = match repository {
+        Some(path) => git_output(path, &["config", "--get", "core.sshCommand"]),
+        None => git_output(Path::new("."), &["config", "--global", "--get", "core.sshCommand"]),
+    };
+    Some(configured.ok().flatten().unwrap_or_else(|| "ssh".to_string()))
+}
+
+/// Runs a command that may talk to a remote, killing it after `timeout` if one is given.
+fn run_remote(command: &mut Command, timeout: Option<Duration>) -> Result<Output> {
+    match timeout {
+        Some(timeout) => output_with_timeout(command, timeout),
+        None => Ok(command.output()?),
+    }
+}
+
+/// Runs `git <args>` in `path` and returns its trimmed output, or `None` if it failed.
+fn git_output(path: &Path, args: &[&str]) -> Result<Option<String>> {
+    let output = Command::new("git").arg("-C").arg(path).args(args).output()?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&output.stdout).trim().to_string()))
+}
+
+/// Runs `git <args>` in `path`, failing with a `GitFailure` if it exits unsuccessfully.
+fn run_git(path: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("git").args(args).current_dir(path).output()?;
+
+    if !output.status.success() {
+        return Err(GitFailure::new(&args.join(" "), &output.stderr).into());
+    }
+
+    Ok(())
+}
+
+/// Detects a multi-step operation from the marker files Git leaves in the git directory.
+fn operation_in_progress(git_dir: &Path) -> Option<Operation> {
+    if git_dir.join("rebase-merge").is_dir() || git_dir.join("rebase-apply").is_dir() {
+        Some(Operation::Rebase)
+    } else if git_dir.join("MERGE_HEAD").is_file() {
+        Some(Operation::Merge)
+    } else if git_dir.join("CHERRY_PICK_HEAD").is_file() {
+        Some(Operation::CherryPick)
+    } else if git_dir.join("REVERT_HEAD").is_file() {
+        Some(Operation::Revert)
+    } else if git_dir.join("BISECT_LOG").is_file() {
+        Some(Operation::Bisect)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::git_utilities::test_support::{commit_file, git, init, write, Fixture};
+
+    fn pull_options(strategy: PullStrategy, autostash: bool) -> PullOptions {
+        PullOptions {
+            strategy,
+            autostash,
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn status_of_fixture_repositories() {
+        let fixture = Fixture::new();
+        for (name, path) in fixture.status_scenarios() {
+            let status = CliBackend.status(&path).unwrap().unwrap();
+            match name {
+                "clean" => {
+                    assert_eq!(status.branch.as_deref(), Some("main"));
+                    assert_eq!(status.upstream.as_deref(), Some("origin/main"));
+                    assert_eq!((status.ahead, status.behind), (Some(0), Some(0)));
+                    assert!(status.conditions().is_empty(), "{:?}", status.conditions());
+                }
+                "dirty" => {
+                    assert_eq!(status.modified, ["README.md"]);
+                    // Like `git status`, untracked directories are listed instead of their files
+                    assert_eq!(status.untracked, ["notes/"]);
+                    assert!(status.staged.is_empty());
+                }
+                "staged" => {
+                    assert_eq!(status.staged, ["README.md", "staged.txt"]);
+                    assert_eq!(status.modified, ["README.md"]);
+                }
+                "conflicted" => {
+                    assert_eq!(status.conflicted, ["README.md"]);
+                    assert_eq!(status.operation, Some(Operation::Merge));
+                }
+                "detached" => {
+                    assert_eq!(status.branch, None);
+                    assert!(status.is_detached());
+                }
+                "ahead-behind" => {
+                    assert_eq!((status.ahead, status.behind), (Some(2), Some(1)));
+                    assert!(status.is_diverged());
+                }
+                "stashed" => {
+                    assert_eq!(status.stashes, 1);
+                    assert!(!status.has_tracked_changes());
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn status_outside_a_repository() {
+        let fixture = Fixture::new();
+        assert!(CliBackend.status(fixture.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn merged_branches_of_the_given_remote() {
+        let fixture = Fixture::new();
+        let local = fixture.repo("local");
+        git(&local, &["checkout", "-q", "-b", "trunk"]);
+        commit_file(&local, "trunk.txt", "trunk\n", "Trunk commit");
+        git(&local, &["branch", "done"]);
+        git(&local, &["checkout", "-q", "main"]);
+
+        let upstream = fixture.path().join("upstream.git");
+        git(fixture.path(), &["init", "-q", "--bare", upstream.to_str().unwrap()]);
+        git(&local, &["remote", "add", "upstream", upstream.to_str().unwrap()]);
+        git(&local, &["push", "-q", "upstream", "trunk"]);
+        git(&local, &["remote", "set-head", "upstream", "trunk"]);
+
+        assert_eq!(default_branch(&local, "upstream").unwrap(), "upstream/trunk");
+        assert_eq!(CliBackend.merged_branches(&local, "upstream").unwrap(), ["done"]);
+
+        // Without that remote, the local `main` is the default branch, and nothing is merged into it
+        assert_eq!(default_branch(&local, "origin").unwrap(), "main");
+        assert!(CliBackend.merged_branches(&local, "origin").unwrap().is_empty());
+    }
+
+    #[test]
+    fn fast_forward_pull_into_diverged_branch() {
+        let fixture = Fixture::new();
+        let (remote, local) = fixture.repo_with_remote("origin.git", "local");
+        fixture.commit_to_remote(&remote, "theirs.txt", "theirs\n");
+        commit_file(&local, "ours.txt", "ours\n", "Our commit");
+        let head = git(&local, &["rev-parse", "HEAD"]);
+
+        let err = CliBackend.pull(&local, pull_options(PullStrategy::FfOnly, false)).unwrap_err();
+        let failure = err.downcast_ref::<GitFailure>().unwrap();
+        assert_eq!(failure.kind, FailureKind::Diverged);
+        assert_eq!(git(&local, &["rev-parse", "HEAD"]), head);
+    }
+
+    #[test]
+    fn rebase_stopped_on_conflicts_is_rolled_back() {
+        let fixture = Fixture::new();
+        let (remote, local) = fixture.repo_with_remote("origin.git", "local");
+        fixture.commit_to_remote(&remote, "README.md", "theirs\n");
+        commit_file(&local, "README.md", "ours\n", "Our commit");
+        let head = git(&local, &["rev-parse", "HEAD"]);
+
+        let err = CliBackend.pull(&local, pull_options(PullStrategy::Rebase, false)).unwrap_err();
+        assert!(err.is::<NeedsIntervention>(), "{:#}", err);
+        let status = CliBackend.status(&local).unwrap().unwrap();
+        assert_eq!(status.operation, None);
+        assert!(status.conflicted.is_empty());
+        assert_eq!(git(&local, &["rev-parse", "HEAD"]), head);
+    }
+
+    #[test]
+    fn autostash_that_conflicts_leaves_the_conflicts() {
+        let fixture = Fixture::new();
+        let (remote, local) = fixture.repo_with_remote("origin.git", "local");
+        fixture.commit_to_remote(&remote, "README.md", "theirs\n");
+        write(&local, "README.md", "ours\n");
+
+        let err = CliBackend.pull(&local, pull_options(PullStrategy::FfOnly, true)).unwrap_err();
+        assert!(err.is::<LeftConflicted>(), "{:#}", err);
+        let status = CliBackend.status(&local).unwrap().unwrap();
+        assert_eq!(status.conflicted, ["README.md"]);
+        assert_eq!(status.stashes, 1);
+    }
+
+    #[test]
+    fn autostash_without_conflicts() {
+        let fixture = Fixture::new();
+        let (remote, local) = fixture.repo_with_remote("origin.git", "local");
+        fixture.commit_to_remote(&remote, "theirs.txt", "theirs\n");
+        write(&local, "README.md", "ours\n");
+
+        CliBackend.pull(&local, pull_options(PullStrategy::FfOnly, true)).unwrap();
+        let status = CliBackend.status(&local).unwrap().unwrap();
+        assert_eq!(status.modified, ["README.md"]);
+        assert_eq!(status.behind, Some(0));
+    }
+
+    #[test]
+    fn stash_pop_that_conflicts_keeps_the_entry() {
+        let fixture = Fixture::new();
+        let local = fixture.repo("local");
+        write(&local, "README.md", "stashed\n");
+        git(&local, &["stash", "-q"]);
+        commit_file(&local, "README.md", "committed\n", "Change after stashing");
+
+        let err = CliBackend.stash_pop(&local).unwrap_err();
+        assert!(err.is::<LeftConflicted>(), "{:#}", err);
+        let status = CliBackend.status(&local).unwrap().unwrap();
+        assert_eq!(status.conflicted, ["README.md"]);
+        assert_eq!(status.stashes, 1);
+    }
+
+    #[test]
+    fn diffstat_before_the_first_commit() {
+        let fixture = Fixture::new();
+        let path = fixture.path().join("new");
+        std::fs::create_dir(&path).unwrap();
+        init(&path);
+        write(&path, "staged.txt", "staged\n");
+        git(&path, &["add", "staged.txt"]);
+        write(&path, "untracked.txt", "untracked\n");
+
+        let selection = ChangeSelection {
+            staged_only: false,
+            untracked: true,
+            paths: Vec::new(),
+        };
+        let lines = CliBackend.diffstat(&path, &selection).unwrap();
+        assert_eq!(lines[0], "staged.txt | 1 +");
+        assert_eq!(lines.last().unwrap(), "untracked.txt (untracked)");
+    }
+}
@@ -0,0 +1,204 @@
+use crate::commands::git_utilities::process;
+use serde::Serialize;
+use std::fmt;
+
+/// The number of stderr lines kept in a `GitFailure` for the report.
+const STDERR_EXCERPT_LINES: usize = 5;
+
+/// An operation that could not be completed automatically and was rolled back, e.g. a rebase
+/// that stopped on conflicts. The repository needs to be handled by hand.
+#[derive(Debug)]
+pub struct NeedsIntervention {
+    pub reason: String,
+}
+
+impl fmt::Display for NeedsIntervention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for NeedsIntervention {}
+
+/// An operation that finished, but left conflicts in the working tree, e.g. re-applying stashed
+/// changes. Nothing was rolled back: the conflicts have to be resolved by hand.
+#[derive(Debug)]
+pub struct LeftConflicted {
+    pub reason: String,
+}
+
+impl fmt::Display for LeftConflicted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for LeftConflicted {}
+
+/// Why a git command failed, as far as can be told from its error output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// Git needed credentials or a host key confirmation, but could not prompt for them.
+    AuthenticationRequired,
+    AuthenticationFailed,
+    NonFastForward,
+    /// A fast-forward-only pull into a branch that has diverged from its upstream.
+    Diverged,
+    HookRejected,
+    NetworkUnreachable,
+    /// Git was killed after running longer than the operation's timeout.
+    TimedOut,
+    Other,
+}
+
+impl FailureKind {
+    /// Classifies a failure from the stderr of the git command. With `non_interactive`, ssh ran
+    /// in batch mode, so a rejected public key may only mean that it could not ask for the
+    /// passphrase of the key.
+    pub fn classify(stderr: &str, non_interactive: bool) -> Self {
+        let stderr = stderr.to_lowercase();
+        let contains_any = |patterns: &[&str]| patterns.iter().any(|p| stderr.contains(p));
+
+        let needed_prompt = contains_any(&[
+            "could not read username",
+            "could not read password",
+            "terminal prompts disabled",
+            "host key verification failed",
+            "no such device or address",
+        ]) || (non_interactive && stderr.contains("permission denied (publickey"));
+
+        if needed_prompt {
+            FailureKind::AuthenticationRequired
+        } else if contains_any(&[
+            "authentication failed",
+            "permission denied",
+            "invalid username or password",
+            "access denied",
+            "the requested url returned error: 403",
+            "the requested url returned error: 401",
+        ]) {
+            FailureKind::AuthenticationFailed
+        } else if contains_any(&["hook declined", "[remote rejected]", "hook failed"]) {
+            FailureKind::HookRejected
+        } else if contains_any(&["non-fast-forward", "fetch first", "tip of your current branch is behind"]) {
+            FailureKind::NonFastForward
+        } else if contains_any(&[
+            "could not resolve host",
+            "connection refused",
+            "connection timed out",
+            "network is unreachable",
+            "unable to access",
+            "could not read from remote repository",
+        ]) {
+            FailureKind::NetworkUnreachable
+        } else {
+            FailureKind::Other
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            FailureKind::AuthenticationRequired => "authentication required",
+            FailureKind::AuthenticationFailed => "authentication failed",
+            FailureKind::NonFastForward => "rejected as non-fast-forward",
+            FailureKind::Diverged => "not possible, the branch has diverged from its upstream",
+            FailureKind::HookRejected => "rejected by a hook",
+            FailureKind::NetworkUnreachable => "remote unreachable",
+            FailureKind::TimedOut => "timed out",
+            FailureKind::Other => "failed",
+        }
+    }
+}
+
+/// A git command that exited unsuccessfully.
+#[derive(Debug)]
+pub struct GitFailure {
+    /// The git subcommand that failed, e.g. `push`.
+    pub command: String,
+    pub kind: FailureKind,
+    /// The last lines of the command's stderr.
+    pub stderr: String,
+}
+
+impl GitFailure {
+    pub fn new(command: &str, stderr: &[u8]) -> Self {
+        let stderr = String::from_utf8_lossy(stderr);
+        let lines: Vec<&str> = stderr
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let excerpt = lines[lines.len().saturating_sub(STDERR_EXCERPT_LINES)..].join("\n");
+
+        Self {
+            command: command.to_string(),
+            kind: FailureKind::classify(&stderr, process::is_non_interactive()),
+            stderr: excerpt,
+        }
+    }
+
+    /// A one-line description of the failure, without the stderr excerpt.
+    pub fn summary(&self) -> String {
+        format!("git {} {}", self.command, self.kind.description())
+    }
+}
+
+impl fmt::Display for GitFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.summary())?;
+        if !self.stderr.is_empty() {
+            write!(f, ": {}", self.stderr)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GitFailure {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_stderr() {
+        let cases = [
+            (
+                "fatal: could not read Username for 'https://github.com': terminal prompts disabled",
+                FailureKind::AuthenticationRequired,
+            ),
+            ("Host key verification failed.", FailureKind::AuthenticationRequired),
+            ("fatal: Authentication failed for 'https://github.com/a/b.git/'", FailureKind::AuthenticationFailed),
+            ("fatal: unable to access '...': The requested URL returned error: 403", FailureKind::AuthenticationFailed),
+            (" ! [remote rejected] main -> main (pre-receive hook declined)", FailureKind::HookRejected),
+            (" ! [rejected]        main -> main (non-fast-forward)", FailureKind::NonFastForward),
+            (" ! [rejected]        main -> main (fetch first)", FailureKind::NonFastForward),
+            (
+                "ssh: Could not resolve hostname example.invalid\nfatal: Could not read from remote repository.",
+                FailureKind::NetworkUnreachable,
+            ),
+            ("fatal: unable to access '...': Connection refused", FailureKind::NetworkUnreachable),
+            ("error: pathspec 'nope' did not match any file(s) known to git", FailureKind::Other),
+            ("", FailureKind::Other),
+        ];
+
+        for (stderr, kind) in cases {
+            assert_eq!(FailureKind::classify(stderr, false), kind, "{}", stderr);
+        }
+    }
+
+    #[test]
+    fn publickey_denial_without_prompts_needs_authentication() {
+        let stderr = "git@github.com: Permission denied (publickey).\nfatal: Could not read from remote repository.";
+        assert_eq!(FailureKind::classify(stderr, false), FailureKind::AuthenticationFailed);
+        assert_eq!(FailureKind::classify(stderr, true), FailureKind::AuthenticationRequired);
+    }
+
+    #[test]
+    fn keeps_the_last_lines_of_stderr() {
+        let stderr = "1\n2\n\n3\n4\n5\n6\n";
+        let failure = GitFailure::new("push", stderr.as_bytes());
+        assert_eq!(failure.stderr, "2\n3\n4\n5\n6");
+        assert_eq!(failure.summary(), "git push failed");
+    }
+}
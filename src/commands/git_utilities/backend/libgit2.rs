@@ -0,0 +1,299 @@
+use crate::commands::git_utilities::backend::cli::CliBackend;
+use crate::commands::git_utilities::backend::{Activity, ChangeSelection, CommitInfo, GitBackend, PullOptions, PushOptions};
+use crate::commands::git_utilities::status::{Operation, RepoStatus};
+use anyhow::Result;
+use git2::{ErrorCode, Repository, RepositoryState, Status, StatusOptions, SubmoduleIgnore, SubmoduleStatus};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+/// Checks repository status in-process with libgit2, which is faster than spawning `git` and
+/// doesn't depend on the user's git config or hooks.
+///
+/// Network operations are delegated to `CliBackend`, so credential helpers and SSH
+/// configuration keep working exactly as they do for the user's own `git`.
+pub struct Libgit2Backend;
+
+impl GitBackend for Libgit2Backend {
+    fn status(&self, path: &Path) -> Result<Option<RepoStatus>> {
+        // 1. Check if it's a git repository with a work tree
+        let mut repo = match Repository::discover(path) {
+            Ok(repo) => repo,
+            Err(err) if err.code() == ErrorCode::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if repo.is_bare() {
+            return Ok(None);
+        }
+
+        let mut status = RepoStatus::default();
+
+        // 2. Read the branch, upstream and ahead/behind counts
+        if !repo.head_detached()? {
+            let head = repo.find_reference("HEAD")?;
+            let branch_ref = head.symbolic_target().map(str::to_string);
+            drop(head);
+
+            if let Some(branch_ref) = branch_ref {
+                status.branch = branch_ref.strip_prefix("refs/heads/").map(str::to_string);
+
+                if let Ok(upstream_ref) = repo.branch_upstream_name(&branch_ref) {
+                    let upstream_ref = upstream_ref.as_str().unwrap_or_default().to_string();
+                    status.upstream = Some(
+                        upstream_ref
+                            .strip_prefix("refs/remotes/")
+                            .unwrap_or(&upstream_ref)
+                            .to_string(),
+                    );
+
+                    // Both ends must resolve: the branch may be unborn or the upstream deleted
+                    if let (Ok(local), Ok(upstream)) = (
+                        repo.refname_to_id(&branch_ref),
+                        repo.refname_to_id(&upstream_ref),
+                    ) {
+                        let (ahead, behind) = repo.graph_ahead_behind(local, upstream)?;
+                        status.ahead = Some(ahead);
+                        status.behind = Some(behind);
+                    }
+                }
+            }
+        }
+
+        // For a branch without upstream, find remote branches that could become one
+        if let (Some(branch), None) = (&status.branch, status.ahead) {
+            for reference in repo.references_glob(&format!("refs/remotes/*/{}", branch))? {
+                if let Some(name) = reference?.shorthand() {
+                    status.matching_remote_branches.push(name.to_string());
+                }
+            }
+        }
+
+        // 3. Find the submodules with problems. Unlike the CLI backend, nested submodules are
+        //    not looked into
+        let mut submodules = HashSet::new();
+        for submodule in repo.submodules()? {
+            let (Some(name), Some(submodule_path)) = (submodule.name(), submodule.path().to_str()) else {
+                continue;
+            };
+            submodules.insert(submodule_path.to_string());
+
+            // A single broken submodule doesn't keep the rest of the status from being read
+            let flags = match repo.submodule_status(name, SubmoduleIgnore::None) {
+                Ok(flags) => flags,
+                Err(err) => {
+                    status.submodule_mut(submodule_path).error = Some(err.message().to_string());
+                    continue;
+                }
+            };
+            let uninitialized = flags.contains(SubmoduleStatus::WD_UNINITIALIZED);
+            let commit_changed = flags.contains(SubmoduleStatus::WD_MODIFIED);
+            let dirty = flags.intersects(
+                SubmoduleStatus::WD_INDEX_MODIFIED | SubmoduleStatus::WD_WD_MODIFIED | SubmoduleStatus::WD_UNTRACKED,
+            );
+            if uninitialized || commit_changed || dirty {
+                let entry = status.submodule_mut(submodule_path);
+                entry.uninitialized = uninitialized;
+                entry.commit_changed = commit_changed;
+                entry.dirty = dirty;
+            }
+        }
+
+        // 4. Classify the changed files
+
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).exclude_submodules(false);
+
+        for entry in repo.statuses(Some(&mut options))?.iter() {
+            let Some(file) = entry.path().map(str::to_string) else {
+                continue;
+            };
+            let flags = entry.status();
+
+            if submodules.contains(&file) {
+                continue;
+            } else if flags.is_conflicted() {
+                status.conflicted.push(file);
+            } else if flags.is_wt_new() {
+                status.untracked.push(file);
+            } else {
+                let staged = Status::INDEX_NEW
+                    | Status::INDEX_MODIFIED
+                    | Status::INDEX_DELETED
+                    | Status::INDEX_RENAMED
+                    | Status::INDEX_TYPECHANGE;
+                let modified = Status::WT_MODIFIED
+                    | Status::WT_DELETED
+                    | Status::WT_RENAMED
+                    | Status::WT_TYPECHANGE;
+
+                if flags.intersects(staged) {
+                    status.staged.push(file.clone());
+                }
+                if flags.intersects(modified) {
+                    status.modified.push(file);
+                }
+            }
+        }
+
+        // 5. Count the stash entries
+        repo.stash_foreach(|_, _, _| {
+            status.stashes += 1;
+            true
+        })?;
+
+        // 6. Look for an interrupted merge, rebase, etc.
+        status.operation = match repo.state() {
+            RepositoryState::Clean => None,
+            RepositoryState::Merge => Some(Operation::Merge),
+            RepositoryState::Revert | RepositoryState::RevertSequence => Some(Operation::Revert),
+            RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+                Some(Operation::CherryPick)
+            }
+            RepositoryState::Bisect => Some(Operation::Bisect),
+            RepositoryState::Rebase
+            | RepositoryState::RebaseInteractive
+            | RepositoryState::RebaseMerge
+            | RepositoryState::ApplyMailbox
+            | RepositoryState::ApplyMailboxOrRebase => Some(Operation::Rebase),
+        };
+
+        Ok(Some(status))
+    }
+
+    fn fetch(&self, path: &Path, prune: bool, timeout: Duration) -> Result<()> {
+        CliBackend.fetch(path, prune, timeout)
+    }
+
+    fn pull(&self, path: &Path, options: PullOptions) -> Result<()> {
+        CliBackend.pull(path, options)
+    }
+
+    fn push(&self, path: &Path, options: &PushOptions) -> Result<()> {
+        CliBackend.push(path, options)
+    }
+
+    fn update_submodules(&self, path: &Path, timeout: Duration) -> Result<()> {
+        CliBackend.update_submodules(path, timeout)
+    }
+
+    fn clone_repo(&self, url: &str, path: &Path, branch: Option<&str>, timeout: Duration) -> Result<()> {
+        CliBackend.clone_repo(url, path, branch, timeout)
+    }
+
+    fn switch_branch(&self, path: &Path, name: &str, create: bool) -> Result<()> {
+        CliBackend.switch_branch(path, name, create)
+    }
+
+    fn merged_branches(&self, path: &Path, remote: &str) -> Result<Vec<String>> {
+        CliBackend.merged_branches(path, remote)
+    }
+
+    fn gone_branches(&self, path: &Path) -> Result<Vec<String>> {
+        CliBackend.gone_branches(path)
+    }
+
+    fn delete_branch(&self, path: &Path, name: &str, force: bool) -> Result<()> {
+        CliBackend.delete_branch(path, name, force)
+    }
+
+    fn commit(&self, path: &Path, message: &str, selection: &ChangeSelection) -> Result<()> {
+        CliBackend.commit(path, message, selection)
+    }
+
+    fn has_changes(&self, path: &Path, selection: &ChangeSelection) -> Result<bool> {
+        CliBackend.has_changes(path, selection)
+    }
+
+    fn diffstat(&self, path: &Path, selection: &ChangeSelection) -> Result<Vec<String>> {
+        CliBackend.diffstat(path, selection)
+    }
+
+    fn stash_push(&self, path: &Path, message: Option<&str>, include_untracked: bool) -> Result<()> {
+        CliBackend.stash_push(path, message, include_untracked)
+    }
+
+    fn stash_pop(&self, path: &Path) -> Result<()> {
+        CliBackend.stash_pop(path)
+    }
+
+    fn stash_list(&self, path: &Path) -> Result<Vec<String>> {
+        CliBackend.stash_list(path)
+    }
+
+    fn last_commit(&self, path: &Path) -> Result<Option<CommitInfo>> {
+        let repo = Repository::discover(path)?;
+        let head = match repo.head() {
+            Ok(head) => head,
+            Err(err) if err.code() == ErrorCode::UnbornBranch => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let commit = head.peel_to_commit()?;
+        let author = commit.author().name().unwrap_or_default().to_string();
+        Ok(Some(CommitInfo {
+            timestamp: commit.time().seconds(),
+            author,
+        }))
+    }
+
+    fn activity(&self, path: &Path, since: i64, remote: &str) -> Result<Activity> {
+        CliBackend.activity(path, since, remote)
+    }
+
+    fn commits(&self, path: &Path, include: &str, exclude: &[&str]) -> Result<Vec<String>> {
+        let repo = Repository::discover(path)?;
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(repo.revparse_single(include)?.peel_to_commit()?.id())?;
+        for revision in exclude {
+            if revision.contains('*') {
+                revwalk.hide_glob(revision)?;
+            } else {
+                revwalk.hide(repo.revparse_single(revision)?.peel_to_commit()?.id())?;
+            }
+        }
+
+        let mut commits = Vec::new();
+        for oid in revwalk {
+            let commit = repo.find_commit(oid?)?;
+            let short_id = commit.as_object().short_id()?;
+            commits.push(format!(
+                "{} {}",
+                short_id.as_str().unwrap_or_default(),
+                commit.summary().unwrap_or_default()
+            ));
+        }
+        Ok(commits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::git_utilities::test_support::Fixture;
+
+    fn sorted(paths: &[String]) -> Vec<String> {
+        let mut paths = paths.to_vec();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn status_matches_the_cli_backend() {
+        let fixture = Fixture::new();
+        for (name, path) in fixture.status_scenarios() {
+            let cli = CliBackend.status(&path).unwrap().unwrap();
+            let libgit2 = Libgit2Backend.status(&path).unwrap().unwrap();
+
+            assert_eq!(cli.branch, libgit2.branch, "branch of {}", name);
+            assert_eq!(cli.upstream, libgit2.upstream, "upstream of {}", name);
+            assert_eq!((cli.ahead, cli.behind), (libgit2.ahead, libgit2.behind), "ahead/behind of {}", name);
+            assert_eq!(sorted(&cli.staged), sorted(&libgit2.staged), "staged files of {}", name);
+            assert_eq!(sorted(&cli.modified), sorted(&libgit2.modified), "modified files of {}", name);
+            assert_eq!(sorted(&cli.untracked), sorted(&libgit2.untracked), "untracked files of {}", name);
+            assert_eq!(sorted(&cli.conflicted), sorted(&libgit2.conflicted), "conflicted files of {}", name);
+            assert_eq!(cli.stashes, libgit2.stashes, "stashes of {}", name);
+            assert_eq!(cli.operation, libgit2.operation, "operation of {}", name);
+            assert_eq!(cli.conditions(), libgit2.conditions(), "conditions of {}", name);
+        }
+    }
+}
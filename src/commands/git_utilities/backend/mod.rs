@@ -0,0 +1,157 @@
+pub mod cli;
+pub mod error;
+#[cfg(feature = "libgit2")]
+pub mod libgit2;
+
+use crate::cli::{GitBackendKind, PullStrategy};
+use crate::commands::git_utilities::status::RepoStatus;
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// How `GitBackend::pull` should integrate the upstream changes.
+#[derive(Debug, Clone, Copy)]
+pub struct PullOptions {
+    pub strategy: PullStrategy,
+    pub autostash: bool,
+    /// Kill git if the pull is still running after this long.
+    pub timeout: Option<Duration>,
+}
+
+/// How `GitBackend::push` should publish the current branch.
+#[derive(Debug, Clone, Default)]
+pub struct PushOptions {
+    /// The remote to push to. Defaults to the branch's remote, `remote.pushDefault`, or the
+    /// repository's only remote, in that order.
+    pub remote: Option<String>,
+    /// Link the pushed branch to the remote branch as its upstream.
+    pub set_upstream: bool,
+    /// Push all tags along with the branch.
+    pub tags: bool,
+    /// Overwrite the remote branch, unless it moved since it was last fetched.
+    pub force_with_lease: bool,
+    /// Kill git if the push is still running after this long.
+    pub timeout: Option<Duration>,
+}
+
+/// Which local changes `GitBackend::commit` records and `GitBackend::diffstat` describes.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSelection {
+    /// Only the changes already in the index. The other fields are ignored when set.
+    pub staged_only: bool,
+    /// Include untracked files, not only changes to tracked ones.
+    pub untracked: bool,
+    /// Restrict the changes to these pathspecs. Empty means the whole working tree.
+    pub paths: Vec<String>,
+}
+
+/// When and by whom a commit was made.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitInfo {
+    /// Seconds since the Unix epoch.
+    pub timestamp: i64,
+    pub author: String,
+}
+
+/// How busy a repository has been and how many branches it carries.
+#[derive(Debug, Clone, Default)]
+pub struct Activity {
+    /// Commits on the checked-out branch made since the start of the window.
+    pub recent_commits: usize,
+    pub local_branches: usize,
+    /// Remote-tracking branches, not counting the `<remote>/HEAD` aliases.
+    pub remote_branches: usize,
+    /// The local branch with the oldest last commit that is not merged into the default branch,
+    /// with that commit's timestamp. `None` when every branch is merged or there is no default
+    /// branch to compare with.
+    pub oldest_unmerged: Option<(String, i64)>,
+}
+
+/// The Git operations the batch commands need, independent of how they are carried out.
+pub trait GitBackend: Send + Sync {
+    /// Checks a single directory to see if it's a Git work tree and what its status is.
+    /// Returns `None` if `path` is not inside a work tree.
+    fn status(&self, path: &Path) -> Result<Option<RepoStatus>>;
+
+    /// Refreshes the remote-tracking refs of a repository, so the ahead/behind counts returned
+    /// by `status` reflect the current state of the remote.
+    fn fetch(&self, path: &Path, prune: bool, timeout: Duration) -> Result<()>;
+
+    /// Integrates the upstream branch into the current branch. If the pull stops half-way, it
+    /// is aborted and a `NeedsIntervention` error is returned. If it succeeds but re-applying
+    /// autostashed changes leaves conflicts, a `LeftConflicted` error is returned. Other failures
+    /// are reported as a `GitFailure`, of the `Diverged` kind when a fast-forward-only pull is
+    /// not possible.
+    fn pull(&self, path: &Path, options: PullOptions) -> Result<()>;
+
+    /// Pushes the current branch. Failures are reported as a `GitFailure`.
+    fn push(&self, path: &Path, options: &PushOptions) -> Result<()>;
+
+    /// Initializes and checks out every submodule, nested ones included, at the commit the
+    /// superproject records. Git is killed if it is still running after `timeout`.
+    fn update_submodules(&self, path: &Path, timeout: Duration) -> Result<()>;
+
+    /// Clones `url` into `path`, checking out `branch` instead of the remote's default branch
+    /// if given. Git is killed if it is still running after `timeout`. Failures are reported as
+    /// a `GitFailure`.
+    fn clone_repo(&self, url: &str, path: &Path, branch: Option<&str>, timeout: Duration) -> Result<()>;
+
+    /// Checks out the local branch `name`, creating it from `HEAD` first if it doesn't exist and
+    /// `create` is set. Failures are reported as a `GitFailure`.
+    fn switch_branch(&self, path: &Path, name: &str, create: bool) -> Result<()>;
+
+    /// Lists the local branches merged into the default branch (the `HEAD` of `remote`, or a
+    /// local `main` or `master`), except the default branch itself and the checked out branch.
+    fn merged_branches(&self, path: &Path, remote: &str) -> Result<Vec<String>>;
+
+    /// Lists the local branches whose upstream branch no longer exists on the remote, except
+    /// the checked out branch.
+    fn gone_branches(&self, path: &Path) -> Result<Vec<String>>;
+
+    /// Deletes a local branch. Without `force`, git refuses to delete a branch that is not
+    /// merged into its upstream or `HEAD`. Failures are reported as a `GitFailure`.
+    fn delete_branch(&self, path: &Path, name: &str, force: bool) -> Result<()>;
+
+    /// Stages the selected changes and commits them. Failures are reported as a `GitFailure`.
+    fn commit(&self, path: &Path, message: &str, selection: &ChangeSelection) -> Result<()>;
+
+    /// Whether the selected changes hold anything to commit. Failures are reported as a
+    /// `GitFailure`.
+    fn has_changes(&self, path: &Path, selection: &ChangeSelection) -> Result<bool>;
+
+    /// Describes the selected changes as `git diff --stat` lines, followed by one line per
+    /// untracked file if they are included.
+    fn diffstat(&self, path: &Path, selection: &ChangeSelection) -> Result<Vec<String>>;
+
+    /// Stashes the local changes, and the untracked files if `include_untracked` is set.
+    fn stash_push(&self, path: &Path, message: Option<&str>, include_untracked: bool) -> Result<()>;
+
+    /// Applies and drops the latest stash entry. If it stops on conflicts, the entry is kept, the
+    /// conflicts are left in the working tree and a `LeftConflicted` error is returned.
+    fn stash_pop(&self, path: &Path) -> Result<()>;
+
+    /// Lists the stash entries, latest first, as `stash@{n}: <description>` lines.
+    fn stash_list(&self, path: &Path) -> Result<Vec<String>>;
+
+    /// Describes the commit checked out, or returns `None` on a branch without commits yet.
+    fn last_commit(&self, path: &Path) -> Result<Option<CommitInfo>>;
+
+    /// Counts the commits made since `since` (seconds since the Unix epoch) and the branches,
+    /// and finds the oldest branch not merged into the default branch, found as for
+    /// `merged_branches`.
+    fn activity(&self, path: &Path, since: i64, remote: &str) -> Result<Activity>;
+
+    /// Lists the commits reachable from `include` but not from any of `exclude` as
+    /// `<short hash> <subject>` lines. Revisions containing `*` are treated as ref globs.
+    fn commits(&self, path: &Path, include: &str, exclude: &[&str]) -> Result<Vec<String>>;
+}
+
+/// Creates the backend selected on the command line.
+pub fn create(kind: GitBackendKind) -> Box<dyn GitBackend> {
+    match kind {
+        GitBackendKind::Cli => Box::new(cli::CliBackend),
+        #[cfg(feature = "libgit2")]
+        GitBackendKind::Libgit2 => Box::new(libgit2::Libgit2Backend),
+    }
+}
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+use crate::cli::{
+    BranchArgs, BranchCommand, BranchSwitchArgs, DeleteMergedArgs, PruneGoneArgs, RemoteOperationArgs, ReportFormat,
+};
+use crate::commands::git_utilities::backend::error::GitFailure;
+use crate::commands::git_utilities::backend::{self, GitBackend};
+use crate::commands::git_utilities::filters::RepoFilter;
+use crate::commands::git_utilities::report::{ActionResult, RepoRecord, Report};
+use crate::commands::git_utilities::status::RepoStatus;
+use crate::commands::git_utilities::utils::{collect_statuses, print_table, run_with_action, run_with_confirmed_action};
+use crate::utils::logger;
+use anyhow::Result;
+
+pub fn run(args: &BranchArgs) -> Result<()> {
+    match &args.command {
+        BranchCommand::Switch(args) => switch(args),
+        BranchCommand::List(args) => list(args),
+        BranchCommand::DeleteMerged(args) => delete_merged(args),
+        BranchCommand::PruneGone(args) => prune_gone(args),
+    }
+}
+
+/// Switches every repository with a clean working tree to the branch. Dirty repositories are
+/// never switched, so no local work is carried over to, or lost on, another branch.
+fn switch(args: &BranchSwitchArgs) -> Result<()> {
+    let backend = backend::create(args.remote.backend);
+
+    run_with_action(
+        &args.remote,
+        |status| {
+            status.has_safe_worktree(args.remote.allow_untracked)
+                && status.branch.as_deref() != Some(args.name.as_str())
+        },
+        |path, _status| {
+            if args.remote.dry_run {
+                let create = if args.create { "--create " } else { "" };
+                return Ok(ActionResult::DryRun {
+                    command: format!("git -C {} switch {}{}", path.display(), create, args.name),
+                    commits: Vec::new(),
+                });
+            }
+
+            logger::debug(&format!("Switching '{}' to '{}'...", path.display(), args.name));
+            backend.switch_branch(&path, &args.name, args.create).inspect_err(|err| {
+                logger::error(&format!("Failed to switch branch in '{}': {:#}", path.display(), err));
+            })?;
+            Ok(ActionResult::Succeeded)
+        },
+    )
+}
+
+/// Prints the current branch of every repository, with its upstream and how far apart they are.
+fn list(args: &RemoteOperationArgs) -> Result<()> {
+    // Keep stdout clean for machine-readable reports
+    if args.format != ReportFormat::Text {
+        logger::init_stderr(true);
+    }
+
+    let backend = backend::create(args.backend);
+    let filter = RepoFilter::new(&args.filters)?;
+    let records = collect_statuses(args, &filter, backend.as_ref())?;
+
+    if args.format != ReportFormat::Text {
+        return Report::new(records).print(args.format);
+    }
+
+    let rows: Vec<[String; 4]> = records.iter().map(table_row).collect();
+    let header = ["REPOSITORY", "BRANCH", "UPSTREAM", "SYNC"].map(str::to_string);
+    print_table(header, &rows);
+
+    Ok(())
+}
+
+fn table_row(record: &RepoRecord) -> [String; 4] {
+    let path = record.path.display().to_string();
+    let Some(status) = &record.status else {
+        let error = record.error.as_deref().unwrap_or("unknown");
+        return [path, format!("({})", error), "-".to_string(), "-".to_string()];
+    };
+
+    let branch = status.branch.clone().unwrap_or_else(|| "(detached)".to_string());
+    let upstream = status.upstream.clone().unwrap_or_else(|| "-".to_string());
+    let sync = match (status.ahead, status.behind) {
+        (Some(0), Some(0)) => "up to date".to_string(),
+        (Some(ahead), Some(0)) => format!("ahead {}", ahead),
+        (Some(0), Some(behind)) => format!("behind {}", behind),
+        (Some(ahead), Some(behind)) => format!("ahead {}, behind {}", ahead, behind),
+        _ => "-".to_string(),
+    };
+
+    [path, branch, upstream, sync]
+}
+
+/// Deletes the local branches merged into the default branch. Their commits are all reachable
+/// from the default branch, so nothing is lost and they are deleted without asking.
+fn delete_merged(args: &DeleteMergedArgs) -> Result<()> {
+    let backend = backend::create(args.remote.backend);
+
+    run_with_action(&args.remote, is_eligible_for_deletion, |path, _status| {
+        let branches = backend.merged_branches(&path, &args.remote_name).inspect_err(|err| {
+            logger::error(&format!("Failed to list merged branches in '{}': {:#}", path.display(), err));
+        })?;
+        delete_branches(backend.as_ref(), path, &branches, true, args.remote.dry_run)
+    })
+}
+
+/// Deletes the local branches whose upstream is gone. Unless `--force` is given, branches with
+/// commits that were never merged are kept, with a warning.
+fn prune_gone(args: &PruneGoneArgs) -> Result<()> {
+    let backend = backend::create(args.remote.backend);
+
+    let on_ok = |path: PathBuf, _status: &RepoStatus| {
+        let branches = backend.gone_branches(&path).inspect_err(|err| {
+            logger::error(&format!("Failed to list gone branches in '{}': {:#}", path.display(), err));
+        })?;
+        delete_branches(backend.as_ref(), path, &branches, args.force, args.remote.dry_run)
+    };
+
+    // Force-deleting can lose commits that only exist on those branches
+    if args.force {
+        run_with_confirmed_action(
+            &args.remote,
+            "Force-delete the branches whose upstream is gone in these repositories?",
+            args.yes,
+            is_eligible_for_deletion,
+            on_ok,
+        )
+    } else {
+        run_with_action(&args.remote, is_eligible_for_deletion, on_ok)
+    }
+}
+
+/// Branches can be deleted regardless of local changes, but not in the middle of a rebase or
+/// similar, where git may still need them.
+fn is_eligible_for_deletion(status: &RepoStatus) -> bool {
+    status.operation.is_none()
+}
+
+fn delete_branches(
+    backend: &dyn GitBackend,
+    path: PathBuf,
+    branches: &[String],
+    force: bool,
+    dry_run: bool,
+) -> anyhow::Result<ActionResult, anyhow::Error> {
+    if branches.is_empty() {
+        logger::debug(&format!("No branch to delete in '{}'", path.display()));
+        return Ok(ActionResult::Succeeded);
+    }
+
+    if dry_run {
+        return Ok(ActionResult::DryRun {
+            command: format!(
+                "git -C {} branch {} {}",
+                path.display(),
+                if force { "-D" } else { "-d" },
+                branches.join(" ")
+            ),
+            commits: Vec::new(),
+        });
+    }
+
+    let mut deleted = Vec::new();
+    let mut unmerged = Vec::new();
+    for branch in branches {
+        match backend.delete_branch(&path, branch, force) {
+            Ok(()) => deleted.push(branch.as_str()),
+            Err(err) if is_not_merged(&err) => unmerged.push(branch.as_str()),
+            Err(err) => {
+                log_deleted(&path, &deleted);
+                logger::error(&format!("Failed to delete branch '{}' in '{}': {:#}", branch, path.display(), err));
+                return Err(err);
+            }
+        }
+    }
+
+    log_deleted(&path, &deleted);
+    if !unmerged.is_empty() {
+        let reason = format!(
+            "not fully merged, pass --force to delete them anyway: {}",
+            unmerged.join(", ")
+        );
+        // Keeping them is the point of not passing --force, so it doesn't fail the run
+        logger::warning(&format!("Kept branches in '{}': {}", path.display(), reason));
+    }
+
+    Ok(ActionResult::Succeeded)
+}
+
+fn log_deleted(path: &Path, deleted: &[&str]) {
+    if !deleted.is_empty() {
+        logger::info(&format!("Deleted branches in '{}': {}", path.display(), deleted.join(", ")));
+    }
+}
+
+/// Whether `git branch -d` refused to delete a branch because it has unmerged commits.
+fn is_not_merged(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<GitFailure>()
+        .is_some_and(|failure| failure.stderr.contains("not fully merged"))
+}
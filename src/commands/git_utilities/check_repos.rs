@@ -1,12 +1,17 @@
-use crate::cli::RemoteOperationArgs;
-use crate::commands::git_utilities::utils::run_with_action;
+use crate::cli::CheckReposArgs;
+use crate::commands::git_utilities::utils::run_check;
+use crate::commands::git_utilities::watch;
 use anyhow::Result;
-use std::path::PathBuf;
 
-pub fn run(args: &RemoteOperationArgs) -> Result<()> {
-    run_with_action(args, on_ok_check)
-}
+pub fn run(args: &CheckReposArgs) -> Result<()> {
+    #[cfg(feature = "tui")]
+    if args.tui {
+        return crate::commands::git_utilities::dashboard::run(&args.remote);
+    }
+
+    if args.watch {
+        return watch::run(args);
+    }
 
-fn on_ok_check(path: PathBuf) -> Result<(), anyhow::Error> {
-    Ok(())
+    run_check(&args.remote)
 }
@@ -0,0 +1,470 @@
+use crate::cli::{CollectArgs, Embed};
+use crate::commands::git_utilities::backend::error::GitFailure;
+use crate::commands::git_utilities::collect::model::{Change, CollectedFile, SkipReason};
+use crate::commands::git_utilities::collect::read::{self, ReadOptions};
+use crate::commands::git_utilities::collect::tokens::TokenEstimator;
+use anyhow::{Context, Result};
+use similar::TextDiff;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Lines of context around each change in the diffs, as git shows by default.
+const CONTEXT_LINES: usize = 3;
+
+/// The mode git gives submodules, which are commits in the tree rather than files.
+const GITLINK_MODE: &str = "160000";
+
+/// The two sides of the git comparison `--changed-since`, `--staged` or `--diff` asks for.
+pub struct Comparison {
+    /// The revision the changes are made on. `None` when staging the first commit, which is made
+    /// on nothing.
+    before: Option<String>,
+    after: Side,
+}
+
+/// Where the changed files are read from.
+enum Side {
+    WorkTree,
+    Index,
+    Revision(String),
+}
+
+/// A path that differs between the two sides of a comparison.
+#[derive(Debug, PartialEq, Eq)]
+struct ChangedPath {
+    path: PathBuf,
+    change: Change,
+    kind: Kind,
+    /// Whether there was a file at the path, or at the one it was renamed from, before the change,
+    /// rather than nothing or a submodule.
+    file_before: bool,
+}
+
+/// What is at a changed path after the change, or before it if it was deleted.
+#[derive(Debug, PartialEq, Eq)]
+enum Kind {
+    /// A file or a symbolic link, read as text.
+    File,
+    /// A submodule, at this commit if git knows it. It doesn't for the working tree.
+    Submodule { commit: Option<String> },
+    /// A path staged with conflicts, which has no staged content to read.
+    Unmerged,
+}
+
+impl Comparison {
+    /// The comparison asked for on the command line, if any. `root` is the collected directory.
+    pub fn from_args(args: &CollectArgs, root: &Path) -> Result<Option<Self>> {
+        if let Some(revision) = &args.changed_since {
+            return Ok(Some(Self {
+                before: Some(revision.clone()),
+                after: Side::WorkTree,
+            }));
+        }
+        if args.staged {
+            let has_head = git(root, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok();
+            return Ok(Some(Self {
+                before: has_head.then(|| "HEAD".to_string()),
+                after: Side::Index,
+            }));
+        }
+        let Some(range) = &args.diff else {
+            return Ok(None);
+        };
+
+        // An empty end of the range stands for HEAD, as it does for git
+        let or_head = |revision: &str| if revision.is_empty() { "HEAD" } else { revision }.to_string();
+        let comparison = if let Some((base, head)) = range.split_once("...") {
+            let (base, head) = (or_head(base), or_head(head));
+            let merge_base = git(root, &["merge-base", &base, &head])?;
+            Self {
+                before: Some(String::from_utf8_lossy(&merge_base).trim().to_string()),
+                after: Side::Revision(head),
+            }
+        } else if let Some((base, head)) = range.split_once("..") {
+            Self {
+                before: Some(or_head(base)),
+                after: Side::Revision(or_head(head)),
+            }
+        } else {
+            Self {
+                before: Some(range.clone()),
+                after: Side::Revision("HEAD".to_string()),
+            }
+        };
+        Ok(Some(comparison))
+    }
+
+    /// The paths that differ between the two sides, relative to `root`, and how.
+    fn changed_paths(&self, root: &Path) -> Result<Vec<ChangedPath>> {
+        // The modes --raw lists tell submodules from files
+        let mut args = vec!["diff", "--raw", "-z", "-M", "--relative", "--no-ext-diff"];
+        match &self.after {
+            Side::WorkTree => args.extend(self.before.as_deref()),
+            // git compares the index with HEAD, or with nothing before the first commit
+            Side::Index => args.push("--cached"),
+            Side::Revision(head) => args.extend(self.before.as_deref().into_iter().chain([head.as_str()])),
+        }
+        args.push("--");
+        let mut changes = parse_raw(&git(root, &args)?);
+
+        // Files git doesn't track yet are changes to the working tree too
+        if let Side::WorkTree = self.after {
+            let untracked = git(root, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+            changes.extend(nul_separated(&untracked).map(|path| ChangedPath {
+                path: PathBuf::from(path),
+                change: Change::Added,
+                kind: Kind::File,
+                file_before: false,
+            }));
+        }
+        Ok(changes)
+    }
+
+    /// Reads a file as it was before the change, or `None` if nothing came before it.
+    fn read_before(&self, root: &Path, path: &Path) -> Result<Option<Vec<u8>>> {
+        match &self.before {
+            Some(revision) => git(root, &["cat-file", "blob", &object_name(revision, path)]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads a file that is not in the working tree.
+    fn read_after(&self, root: &Path, path: &Path) -> Result<Vec<u8>> {
+        let revision = match &self.after {
+            Side::Revision(head) => head.as_str(),
+            // An empty revision names the file in the index
+            Side::Index | Side::WorkTree => "",
+        };
+        git(root, &["cat-file", "blob", &object_name(revision, path)])
+    }
+}
+
+/// Reads the files that changed between the two sides of `comparison`, as they are after the
+/// change, with their diffs if `embed` asks for them. Only the paths `keep` accepts are read.
+pub fn collect(
+    root: &Path,
+    comparison: &Comparison,
+    embed: Embed,
+    keep: &dyn Fn(&Path) -> bool,
+    options: &ReadOptions,
+    estimator: &dyn TokenEstimator,
+) -> Result<Vec<CollectedFile>> {
+    let mut files = Vec::new();
+
+    for ChangedPath {
+        path,
+        change,
+        kind,
+        file_before,
+    } in comparison.changed_paths(root)?
+    {
+        // Nothing is left of a deleted file but its diff
+        if !keep(&path) || (change == Change::Deleted && embed == Embed::Content) {
+            continue;
+        }
+
+        // 1. Read the file as it is after the change. Submodules and conflicts have nothing to read
+        let mut file = match (kind, &change, &comparison.after) {
+            (Kind::Submodule { commit }, _, _) => {
+                CollectedFile::skipped(path.clone(), 0, None, SkipReason::Submodule { commit })
+            }
+            (Kind::Unmerged, _, _) => CollectedFile::skipped(path.clone(), 0, None, SkipReason::Unmerged),
+            (Kind::File, Change::Deleted, _) => read::from_bytes(path.clone(), Vec::new(), options, estimator),
+            (Kind::File, _, Side::WorkTree) => read::read(&root.join(&path), path.clone(), options, estimator),
+            (Kind::File, _, _) => {
+                let bytes = comparison.read_after(root, &path)?;
+                read::from_bytes(path.clone(), bytes, options, estimator)
+            }
+        };
+        if file.skip_reason().is_some() {
+            file.change = Some(change);
+            files.push(file);
+            continue;
+        }
+
+        // 2. Diff it with the file as it was before, unless that was not text
+        let diff = if embed == Embed::Content {
+            None
+        } else {
+            let before_path = match &change {
+                _ if !file_before => None,
+                Change::Renamed { from } => Some(from.as_path()),
+                _ => Some(path.as_path()),
+            };
+            let before_bytes = match before_path {
+                Some(before_path) => comparison.read_before(root, before_path)?,
+                None => None,
+            };
+            // A file that wasn't there before is diffed with /dev/null
+            let before_path = before_path.filter(|_| before_bytes.is_some());
+            let before = match before_bytes {
+                Some(bytes) => read::text(bytes, options.encoding).ok(),
+                None => Some(String::new()),
+            };
+            let after_path = (change != Change::Deleted).then_some(path.as_path());
+            before
+                .map(|before| unified_diff(before_path, &before, after_path, &file.content))
+                .filter(|diff| !diff.is_empty())
+        };
+
+        file.attach_change(change, diff, embed != Embed::Diff, estimator);
+        files.push(file);
+    }
+
+    Ok(files)
+}
+
+/// The diff between two versions of a file, with the headers git would give it. A missing side
+/// is `/dev/null`.
+fn unified_diff(before_path: Option<&Path>, before: &str, after_path: Option<&Path>, after: &str) -> String {
+    let header = |prefix: &str, path: Option<&Path>| match path {
+        Some(path) => format!("{}/{}", prefix, slash_path(path)),
+        None => "/dev/null".to_string(),
+    };
+
+    TextDiff::from_lines(before, after)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(&header("a", before_path), &header("b", after_path))
+        .to_string()
+}
+
+/// Parses the output of `git diff --raw -z`: for each change, a
+/// `:<old mode> <new mode> <old object> <new object> <status>` field, then the path, or the old
+/// and the new path of a rename.
+fn parse_raw(output: &[u8]) -> Vec<ChangedPath> {
+    let mut fields = nul_separated(output);
+    let mut changes = Vec::new();
+
+    while let (Some(header), Some(path)) = (fields.next(), fields.next()) {
+        let header: Vec<&str> = header.trim_start_matches(':').split(' ').collect();
+        let [old_mode, new_mode, _, new_object, status] = header[..] else {
+            break;
+        };
+        let (path, change) = match status.chars().next() {
+            Some('A') => (path, Change::Added),
+            Some('D') => (path, Change::Deleted),
+            // Renames are followed by the new path
+            Some('R') => match fields.next() {
+                Some(to) => (
+                    to,
+                    Change::Renamed {
+                        from: PathBuf::from(path),
+                    },
+                ),
+                None => break,
+            },
+            _ => (path, Change::Modified),
+        };
+
+        // A missing side has an all-zero mode, and an object git hasn't hashed an all-zero name
+        let is_zero = |field: &str| field.bytes().all(|byte| byte == b'0');
+        let kind = if status.starts_with('U') {
+            Kind::Unmerged
+        } else if new_mode == GITLINK_MODE || (change == Change::Deleted && old_mode == GITLINK_MODE) {
+            Kind::Submodule {
+                commit: (!is_zero(new_object)).then(|| new_object.to_string()),
+            }
+        } else {
+            Kind::File
+        };
+        changes.push(ChangedPath {
+            path: PathBuf::from(path),
+            change,
+            kind,
+            file_before: !is_zero(old_mode) && old_mode != GITLINK_MODE,
+        });
+    }
+    changes
+}
+
+fn nul_separated(output: &[u8]) -> impl Iterator<Item = String> + '_ {
+    output
+        .split(|byte| *byte == 0)
+        .filter(|field| !field.is_empty())
+        .map(|field| String::from_utf8_lossy(field).into_owned())
+}
+
+/// Names the file at `path` in `revision`, or in the index if `revision` is empty. The `./`
+/// makes git take the path as relative to the directory it runs in, not the repository root.
+fn object_name(revision: &str, path: &Path) -> String {
+    format!("{}:./{}", revision, slash_path(path))
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Runs `git <args>` in `root` and returns what it printed, failing with a `GitFailure` if it
+/// exits unsuccessfully.
+fn git(root: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        return Err(GitFailure::new(args[0], &output.stderr).into());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{TextEncoding, Tokenizer};
+    use crate::commands::git_utilities::collect::tokens::estimator;
+    use crate::commands::git_utilities::test_support::{self, commit_file, git_fails, write, Fixture};
+    use clap::Parser;
+
+    fn changed(path: &str, change: Change, kind: Kind, file_before: bool) -> ChangedPath {
+        ChangedPath {
+            path: PathBuf::from(path),
+            change,
+            kind,
+            file_before,
+        }
+    }
+
+    fn collect_changes(root: &Path, args: &[&str]) -> Vec<CollectedFile> {
+        let args = CollectArgs::parse_from(["collect"].iter().chain(args));
+        let comparison = Comparison::from_args(&args, root).unwrap().unwrap();
+        let options = ReadOptions {
+            max_file_size: 0,
+            encoding: TextEncoding::Utf8,
+        };
+        let estimator = estimator(Tokenizer::Chars);
+        collect(root, &comparison, args.embed, &|_| true, &options, estimator.as_ref()).unwrap()
+    }
+
+    #[test]
+    fn parses_changes_to_files() {
+        let output = concat!(
+            ":000000 100644 0000000 1111111 A\0new.txt\0",
+            ":100644 100644 2222222 3333333 M\0src/lib.rs\0",
+            ":100644 000000 4444444 0000000 D\0old.txt\0",
+            ":100644 100644 5555555 6666666 R087\0a.rs\0b.rs\0",
+            ":100644 100644 7777777 0000000 M\0dirty.txt\0",
+        );
+
+        assert_eq!(
+            parse_raw(output.as_bytes()),
+            [
+                changed("new.txt", Change::Added, Kind::File, false),
+                changed("src/lib.rs", Change::Modified, Kind::File, true),
+                changed("old.txt", Change::Deleted, Kind::File, true),
+                changed(
+                    "b.rs",
+                    Change::Renamed {
+                        from: PathBuf::from("a.rs")
+                    },
+                    Kind::File,
+                    true
+                ),
+                changed("dirty.txt", Change::Modified, Kind::File, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_changes_to_submodules_and_conflicts() {
+        let submodule = |commit: Option<&str>| Kind::Submodule {
+            commit: commit.map(str::to_string),
+        };
+        let output = concat!(
+            ":160000 160000 1111111 2222222 M\0staged\0",
+            ":160000 160000 1111111 0000000 M\0checked-out\0",
+            ":000000 160000 0000000 3333333 A\0added\0",
+            ":160000 000000 4444444 0000000 D\0deleted\0",
+            ":160000 100644 5555555 6666666 T\0now-a-file\0",
+            ":100644 160000 7777777 8888888 T\0now-a-submodule\0",
+            ":100644 000000 9999999 0000000 U\0conflicted.txt\0",
+        );
+
+        assert_eq!(
+            parse_raw(output.as_bytes()),
+            [
+                changed("staged", Change::Modified, submodule(Some("2222222")), false),
+                changed("checked-out", Change::Modified, submodule(None), false),
+                changed("added", Change::Added, submodule(Some("3333333")), false),
+                changed("deleted", Change::Deleted, submodule(None), false),
+                changed("now-a-file", Change::Modified, Kind::File, false),
+                changed("now-a-submodule", Change::Modified, submodule(Some("8888888")), true),
+                changed("conflicted.txt", Change::Modified, Kind::Unmerged, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_changed_submodules_without_reading_them() {
+        let fixture = Fixture::new();
+        let submodule = fixture.repo("submodule");
+        let repo = fixture.repo("repo");
+        test_support::git(
+            &repo,
+            &["-c", "protocol.file.allow=always", "submodule", "add", "-q", submodule.to_str().unwrap(), "sub"],
+        );
+        test_support::git(&repo, &["commit", "-q", "-m", "Add the submodule"]);
+        commit_file(&submodule, "new.txt", "new\n", "Submodule commit");
+        test_support::git(&repo.join("sub"), &["pull", "-q"]);
+        let commit = test_support::git(&repo.join("sub"), &["rev-parse", "--short", "HEAD"]);
+
+        // The working tree has no commit to show for the submodule
+        let files = collect_changes(&repo, &["--changed-since", "HEAD", "--embed", "both"]);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("sub"));
+        assert_eq!(files[0].change, Some(Change::Modified));
+        assert_eq!(files[0].skip_reason(), Some(&SkipReason::Submodule { commit: None }));
+
+        test_support::git(&repo, &["commit", "-q", "-am", "Update the submodule"]);
+        for args in [["--diff", "HEAD~1"], ["--diff", "HEAD~1..HEAD"]] {
+            let files = collect_changes(&repo, &[args[0], args[1], "--embed", "diff"]);
+            assert_eq!(files.len(), 1);
+            assert_eq!(
+                files[0].skip_reason(),
+                Some(&SkipReason::Submodule {
+                    commit: Some(commit.clone())
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn records_conflicted_staged_files_without_reading_them() {
+        let fixture = Fixture::new();
+        let repo = fixture.repo("repo");
+        test_support::git(&repo, &["checkout", "-q", "-b", "other"]);
+        commit_file(&repo, "README.md", "theirs\n", "Change on other");
+        test_support::git(&repo, &["checkout", "-q", "main"]);
+        commit_file(&repo, "README.md", "ours\n", "Change on main");
+        git_fails(&repo, &["merge", "-q", "other"]);
+
+        let files = collect_changes(&repo, &["--staged", "--embed", "both"]);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("README.md"));
+        assert_eq!(files[0].skip_reason(), Some(&SkipReason::Unmerged));
+    }
+
+    #[test]
+    fn collects_the_changes_staged_for_the_first_commit() {
+        let fixture = Fixture::new();
+        let repo = fixture.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        test_support::init(&repo);
+        write(&repo, "first.txt", "first\n");
+        test_support::git(&repo, &["add", "first.txt"]);
+
+        let files = collect_changes(&repo, &["--staged", "--embed", "both"]);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].change, Some(Change::Added));
+        assert_eq!(files[0].content, "first\n");
+        assert_eq!(
+            files[0].diff.as_deref(),
+            Some("--- /dev/null\n+++ b/first.txt\n@@ -0,0 +1 @@\n+first\n")
+        );
+    }
+}
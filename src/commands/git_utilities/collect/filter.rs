@@ -0,0 +1,144 @@
+use crate::cli::CollectArgs;
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::types::{Types, TypesBuilder};
+use std::path::Path;
+
+/// Narrows the files the walker yields down to those asked for with `--include`, `--type` and
+/// `--exclude-type`.
+///
+/// It only sees files that are not already excluded by `--ignore`, `--ignore-all` or the ignore
+/// files, so those always take precedence. Then `--exclude-type` removes files, and finally only
+/// files matching one of the `--include` globs, if any, and one of the `--type` types, if any, are
+/// kept.
+pub struct FileFilter {
+    include: Option<GlobSet>,
+    types: Option<Types>,
+    exclude_types: Option<Types>,
+}
+
+impl FileFilter {
+    pub fn new(args: &CollectArgs) -> Result<Self> {
+        let include = if args.include.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for pattern in &args.include {
+                // Like in .gitignore, a pattern without a slash matches the file name at any depth
+                let pattern = if pattern.contains('/') {
+                    pattern.trim_start_matches('/').to_string()
+                } else {
+                    format!("**/{}", pattern)
+                };
+                let glob = GlobBuilder::new(&pattern)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("Invalid include pattern: {}", pattern))?;
+                builder.add(glob);
+            }
+            Some(builder.build().context("Failed to build the include patterns")?)
+        };
+
+        let types = if args.types.is_empty() {
+            None
+        } else {
+            let mut builder = TypesBuilder::new();
+            builder.add_defaults();
+            for name in &args.types {
+                builder.select(name);
+            }
+            Some(builder.build().context("Failed to select the file types")?)
+        };
+
+        let exclude_types = if args.exclude_types.is_empty() {
+            None
+        } else {
+            let mut builder = TypesBuilder::new();
+            builder.add_defaults();
+            for name in &args.exclude_types {
+                builder.negate(name);
+            }
+            Some(builder.build().context("Failed to exclude the file types")?)
+        };
+
+        Ok(Self {
+            include,
+            types,
+            exclude_types,
+        })
+    }
+
+    /// Whether to collect the file at `path`, relative to the collected directory.
+    pub fn matches(&self, path: &Path) -> bool {
+        if let Some(exclude_types) = &self.exclude_types {
+            if exclude_types.matched(path, false).is_ignore() {
+                return false;
+            }
+        }
+
+        let included = self.include.as_ref().is_none_or(|include| include.is_match(path));
+        let typed = self
+            .types
+            .as_ref()
+            .is_none_or(|types| types.matched(path, false).is_whitelist());
+        included && typed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn filter(args: &[&str]) -> FileFilter {
+        let args = CollectArgs::parse_from(["collect"].iter().chain(args));
+        FileFilter::new(&args).unwrap()
+    }
+
+    fn matching<'a>(filter: &FileFilter, paths: &[&'a str]) -> Vec<&'a str> {
+        paths.iter().copied().filter(|path| filter.matches(Path::new(path))).collect()
+    }
+
+    const PATHS: &[&str] = &["Cargo.toml", "src/main.rs", "src/cli/args.rs", "docs/guide.md", "package-lock.json"];
+
+    #[test]
+    fn keeps_everything_without_options() {
+        assert_eq!(matching(&filter(&[]), PATHS), PATHS);
+    }
+
+    #[test]
+    fn include_globs_match_names_at_any_depth_or_paths_from_the_root() {
+        assert_eq!(matching(&filter(&["--include", "*.rs"]), PATHS), ["src/main.rs", "src/cli/args.rs"]);
+        assert_eq!(matching(&filter(&["--include", "src/*.rs"]), PATHS), ["src/main.rs"]);
+        assert_eq!(
+            matching(&filter(&["--include", "/docs/**", "Cargo.toml"]), PATHS),
+            ["Cargo.toml", "docs/guide.md"]
+        );
+    }
+
+    #[test]
+    fn selects_and_excludes_types() {
+        assert_eq!(
+            matching(&filter(&["--type", "rust,toml"]), PATHS),
+            ["Cargo.toml", "src/main.rs", "src/cli/args.rs"]
+        );
+        assert_eq!(
+            matching(&filter(&["--exclude-type", "json,markdown"]), PATHS),
+            ["Cargo.toml", "src/main.rs", "src/cli/args.rs"]
+        );
+    }
+
+    #[test]
+    fn files_must_match_both_include_and_type() {
+        assert_eq!(
+            matching(&filter(&["--type", "rust,toml", "--include", "src/**"]), PATHS),
+            ["src/main.rs", "src/cli/args.rs"]
+        );
+        assert!(matching(&filter(&["--type", "rust", "--include", "docs/**"]), PATHS).is_empty());
+    }
+
+    #[test]
+    fn excluded_types_win_over_include() {
+        assert!(matching(&filter(&["--include", "src/**", "--exclude-type", "rust"]), PATHS).is_empty());
+    }
+}
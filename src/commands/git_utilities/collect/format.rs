@@ -0,0 +1,370 @@
+use crate::cli::CollectFormat;
+use crate::commands::git_utilities::collect::model::{CollectedFile, Inclusion, SkipReason};
+use crate::commands::git_utilities::utils::human_size;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::io::Write;
+use std::path::Path;
+
+/// Writes the files to `out` in `format`: the content of those that have one, and the metadata
+/// of the skipped ones. The files must already be sorted by path.
+pub fn write(files: &[&CollectedFile], format: CollectFormat, out: &mut dyn Write) -> Result<()> {
+    match format {
+        CollectFormat::Json => write_json(files, out),
+        CollectFormat::JsonFlat => write_json_flat(files, out),
+        CollectFormat::Markdown => write_markdown(files, out),
+        CollectFormat::Xml => write_xml(files, out),
+        CollectFormat::Txt => write_txt(files, out),
+    }
+}
+
+/// The extension of the files `format` produces, for the default output file name.
+pub fn extension(format: CollectFormat) -> &'static str {
+    match format {
+        CollectFormat::Json | CollectFormat::JsonFlat => "json",
+        CollectFormat::Markdown => "md",
+        CollectFormat::Xml => "xml",
+        CollectFormat::Txt => "txt",
+    }
+}
+
+/// The content and the diff of the file, as far as they go into the output, with the truncation
+/// note after the last of them.
+fn sections(file: &CollectedFile) -> (Option<String>, Option<String>) {
+    let mut content = file.shows_content().then(|| file.content.clone());
+    let mut diff = file.diff.clone();
+    if let Some(last) = diff.as_mut().or(content.as_mut()) {
+        if let Some(note) = file.note() {
+            *last = format!("{}\n{}", last.trim_end(), note);
+        }
+    }
+    (content, diff)
+}
+
+/// A short description of a skipped file, e.g. "skipped (binary), 1.2 MiB, image/png".
+fn skipped_summary(file: &CollectedFile, reason: &SkipReason) -> String {
+    let mut summary = format!("skipped ({}), {}", reason, human_size(file.size));
+    if let Some(mime_type) = file.mime_type {
+        summary.push_str(", ");
+        summary.push_str(mime_type);
+    }
+    summary
+}
+
+/// The path of the file, followed by how it changed if it did, e.g. "src/lib.rs (modified)".
+fn title(file: &CollectedFile) -> String {
+    match &file.change {
+        Some(change) => format!("{} ({})", display_path(&file.path), change),
+        None => display_path(&file.path),
+    }
+}
+
+/// One object per directory, with the contents of its files keyed by file name. Skipped files
+/// map to an object with their metadata instead, and changed files with a diff or without content
+/// to an object with their change, content and diff.
+fn write_json(files: &[&CollectedFile], out: &mut dyn Write) -> Result<()> {
+    let mut root_map = Map::new();
+    for file in files {
+        insert_file_content(&mut root_map, file);
+    }
+
+    serde_json::to_writer_pretty(&mut *out, &Value::Object(root_map)).context("Failed to write JSON to output file")?;
+    Ok(())
+}
+
+fn insert_file_content(map: &mut Map<String, Value>, file: &CollectedFile) {
+    let mut current_map = map;
+    let components: Vec<_> = file.path.components().collect();
+
+    if let Some((filename_component, parent_components)) = components.split_last() {
+        // Create nested directories
+        for component in parent_components {
+            let dir_name = component.as_os_str().to_string_lossy().to_string();
+            let entry = current_map
+                .entry(dir_name)
+                .or_insert_with(|| Value::Object(Map::new()));
+            current_map = entry.as_object_mut().unwrap();
+        }
+
+        // Insert file content, or the metadata of a skipped file
+        let filename = filename_component.as_os_str().to_string_lossy().to_string();
+        let change = file.change.as_ref().map(|change| change.to_string());
+        let value = match (file.skip_reason(), sections(file)) {
+            (Some(reason), _) => json!({
+                "skipped": reason.to_string(),
+                "size": file.size,
+                "mime_type": file.mime_type,
+                "change": change,
+            }),
+            (None, (Some(content), None)) => Value::String(content),
+            (None, (content, diff)) => json!({
+                "change": change,
+                "content": content,
+                "diff": diff,
+            }),
+        };
+        current_map.insert(filename, value);
+    }
+}
+
+/// An entry of the `json-flat` format.
+#[derive(Serialize)]
+struct FlatEntry<'a> {
+    path: String,
+    size: u64,
+    mime_type: Option<&'static str>,
+    /// `None` for skipped files, and when the diff replaces the content.
+    content: Option<&'a str>,
+    tokens: usize,
+    /// The tokens of the whole file, when `content` was truncated.
+    original_tokens: Option<usize>,
+    /// Why the content was left out, for skipped files.
+    skipped: Option<String>,
+    /// How the file changed, when collecting the files changed in git.
+    change: Option<String>,
+    diff: Option<&'a str>,
+}
+
+/// An array with one object per file, with its path relative to the collected directory.
+fn write_json_flat(files: &[&CollectedFile], out: &mut dyn Write) -> Result<()> {
+    let entries: Vec<FlatEntry> = files
+        .iter()
+        .map(|file| FlatEntry {
+            path: display_path(&file.path),
+            size: file.size,
+            mime_type: file.mime_type,
+            content: (file.has_content() && file.shows_content()).then_some(file.content.as_str()),
+            tokens: file.tokens,
+            original_tokens: match file.inclusion {
+                Inclusion::Truncated { original_tokens } => Some(original_tokens),
+                _ => None,
+            },
+            skipped: file.skip_reason().map(|reason| reason.to_string()),
+            change: file.change.as_ref().map(|change| change.to_string()),
+            diff: file.diff.as_deref(),
+        })
+        .collect();
+
+    serde_json::to_writer_pretty(&mut *out, &entries).context("Failed to write JSON to output file")?;
+    Ok(())
+}
+
+/// A heading with the path of each file, followed by its content and its diff in fenced code
+/// blocks.
+fn write_markdown(files: &[&CollectedFile], out: &mut dyn Write) -> Result<()> {
+    for (index, file) in files.iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+
+        writeln!(out, "## `{}`", title(file))?;
+        if let Some(reason) = file.skip_reason() {
+            writeln!(out)?;
+            writeln!(out, "_{}_", capitalize(&skipped_summary(file, reason)))?;
+            continue;
+        }
+
+        if file.shows_content() {
+            writeln!(out)?;
+            write_fenced(out, &file.content, file.language().unwrap_or_default())?;
+        }
+        if let Some(diff) = &file.diff {
+            writeln!(out)?;
+            write_fenced(out, diff, "diff")?;
+        }
+        if let Some(note) = file.note() {
+            writeln!(out)?;
+            writeln!(out, "_{}_", note)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_fenced(out: &mut dyn Write, text: &str, language: &str) -> Result<()> {
+    // A fence longer than any run of backticks in the text can't be closed by it
+    let fence = "`".repeat(longest_backtick_run(text).max(2) + 1);
+    writeln!(out, "{}{}", fence, language)?;
+    write_text(out, text)?;
+    writeln!(out, "{}", fence)?;
+    Ok(())
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn longest_backtick_run(content: &str) -> usize {
+    content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Each file in a `<file path="...">` element. Contents go in CDATA sections rather than being
+/// escaped, so they read as they are, yet can't end the element early. Skipped files are empty
+/// elements with their metadata as attributes, and files with a diff hold it in a `<diff>`
+/// element, after their content in a `<content>` one.
+fn write_xml(files: &[&CollectedFile], out: &mut dyn Write) -> Result<()> {
+    writeln!(out, "<files>")?;
+    for file in files {
+        write!(out, "<file path=\"{}\"", escape_attribute(&display_path(&file.path)))?;
+        if let Some(change) = &file.change {
+            write!(out, " change=\"{}\"", escape_attribute(&change.to_string()))?;
+        }
+
+        if let Some(reason) = file.skip_reason() {
+            write!(out, " skipped=\"{}\" size=\"{}\"", escape_attribute(&reason.to_string()), file.size)?;
+            if let Some(mime_type) = file.mime_type {
+                write!(out, " mime-type=\"{}\"", escape_attribute(mime_type))?;
+            }
+            writeln!(out, "/>")?;
+            continue;
+        }
+
+        writeln!(out, ">")?;
+        match sections(file) {
+            (Some(content), None) => write_cdata(out, &content)?,
+            (content, diff) => {
+                if let Some(content) = content {
+                    writeln!(out, "<content>")?;
+                    write_cdata(out, &content)?;
+                    writeln!(out, "</content>")?;
+                }
+                if let Some(diff) = diff {
+                    writeln!(out, "<diff>")?;
+                    write_cdata(out, &diff)?;
+                    writeln!(out, "</diff>")?;
+                }
+            }
+        }
+        writeln!(out, "</file>")?;
+    }
+    writeln!(out, "</files>")?;
+    Ok(())
+}
+
+/// Writes `text` in a CDATA section. A `]]>` in it would end the section, so the section is
+/// closed between its `]]` and `>`, and the `>` starts a new one.
+fn write_cdata(out: &mut dyn Write, text: &str) -> Result<()> {
+    write!(out, "<![CDATA[")?;
+    write!(out, "{}", text.replace("]]>", "]]]]><![CDATA[>"))?;
+    writeln!(out, "]]>")?;
+    Ok(())
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The contents one after another, each under a `==> path <==` header like `head` prints, and
+/// followed by its diff if it has one.
+fn write_txt(files: &[&CollectedFile], out: &mut dyn Write) -> Result<()> {
+    for (index, file) in files.iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+
+        writeln!(out, "==> {} <==", title(file))?;
+        if let Some(reason) = file.skip_reason() {
+            writeln!(out, "[{}]", skipped_summary(file, reason))?;
+            continue;
+        }
+
+        let (content, diff) = sections(file);
+        if let Some(content) = &content {
+            write_text(out, content)?;
+        }
+        if let Some(diff) = diff {
+            if content.is_some() {
+                writeln!(out)?;
+            }
+            write_text(out, &diff)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `text`, ending it with a newline if it doesn't already.
+fn write_text(out: &mut dyn Write, text: &str) -> Result<()> {
+    write!(out, "{}", text)?;
+    if !text.is_empty() && !text.ends_with('\n') {
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// The path with `/` separators on every platform, as models and most tools expect.
+fn display_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Tokenizer;
+    use crate::commands::git_utilities::collect::tokens::estimator;
+    use std::path::PathBuf;
+
+    fn file(path: &str, content: &str) -> CollectedFile {
+        let estimator = estimator(Tokenizer::Chars);
+        CollectedFile::new(PathBuf::from(path), content.len() as u64, None, content.to_string(), estimator.as_ref())
+    }
+
+    fn render(files: &[CollectedFile], format: CollectFormat) -> String {
+        let files: Vec<&CollectedFile> = files.iter().collect();
+        let mut out = Vec::new();
+        write(&files, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn xml_contents_cant_end_their_element() {
+        let files = [
+            file("page.xml", "<file path=\"fake\">\n</file>\n</files>\n"),
+            file("cdata.txt", "a]]>b"),
+        ];
+
+        assert_eq!(
+            render(&files, CollectFormat::Xml),
+            concat!(
+                "<files>\n",
+                "<file path=\"page.xml\">\n",
+                "<![CDATA[<file path=\"fake\">\n</file>\n</files>\n]]>\n",
+                "</file>\n",
+                "<file path=\"cdata.txt\">\n",
+                "<![CDATA[a]]]]><![CDATA[>b]]>\n",
+                "</file>\n",
+                "</files>\n",
+            )
+        );
+    }
+
+    #[test]
+    fn xml_attributes_are_escaped() {
+        let files = [file("a \"quoted\" & <odd>.txt", "")];
+        assert!(render(&files, CollectFormat::Xml)
+            .contains("<file path=\"a &quot;quoted&quot; &amp; &lt;odd&gt;.txt\">"));
+    }
+
+    #[test]
+    fn markdown_fences_are_longer_than_the_backticks_inside() {
+        let files = [file("README.md", "Use ````rust fences.\n")];
+        assert_eq!(
+            render(&files, CollectFormat::Markdown),
+            "## `README.md`\n\n`````markdown\nUse ````rust fences.\n`````\n"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+mod changes;
+mod filter;
+mod format;
+mod model;
+mod read;
+mod tokens;
+
+use crate::cli::CollectArgs;
+use crate::utils::logger;
+use anyhow::{Context, Result};
+use changes::Comparison;
+use filter::FileFilter;
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use model::{CollectedFile, Inclusion};
+use read::ReadOptions;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tokens::Budget;
+
+pub fn run(args: &CollectArgs) -> Result<()> {
+    let output_file = args
+        .output_file
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("content.{}", format::extension(args.format))));
+
+    // 1. Set up the directory walker using the provided arguments
+    let mut walk_builder = WalkBuilder::new(&args.path);
+
+    // Use an OverrideBuilder to add programmatic ignore patterns
+    let mut override_builder = OverrideBuilder::new(&args.path);
+
+    // Add patterns from --ignore-all
+    for pattern in &args.ignore_all {
+        // '!' makes it an ignore rule, not a whitelist
+        override_builder.add(&format!("!**/{}", pattern))
+            .context(format!("Failed to add ignore-all pattern: {}", pattern))?;
+    }
+
+    // Add patterns from --ignore
+    for pattern in &args.ignore {
+        override_builder.add(&format!("!{}", pattern))
+            .context(format!("Failed to add ignore pattern: {}", pattern))?;
+    }
+
+    // Always ignore the output file itself
+    if let Some(output_filename) = output_file.to_str() {
+        override_builder.add(&format!("!{}", output_filename))
+            .context(format!("Failed to ignore output file: {}", output_filename))?;
+    }
+
+    // Build the override rules and apply them to the WalkBuilder
+    let overrides = override_builder.build()?;
+    walk_builder.overrides(overrides.clone());
+
+    // Narrow the files the walker yields down with --include, --type and --exclude-type
+    let filter = FileFilter::new(args)?;
+
+    // 2. Find the files to collect, from git when asked for the changed ones and by walking the
+    // directory otherwise. Read the text ones, recording why the others were skipped.
+    let estimator = tokens::estimator(args.tokenizer);
+    let read_options = ReadOptions {
+        max_file_size: args.max_file_size,
+        encoding: args.encoding,
+    };
+    let mut files = Vec::new();
+
+    if let Some(comparison) = Comparison::from_args(args, &args.path)? {
+        let keep = |path: &Path| !is_overridden(&overrides, path) && filter.matches(path);
+        files = changes::collect(
+            &args.path,
+            &comparison,
+            args.embed,
+            &keep,
+            &read_options,
+            estimator.as_ref(),
+        )?;
+    } else {
+        for result in walk_builder.build() {
+            let entry = result.context("Failed to process a directory entry")?;
+            if entry.file_type().is_some_and(|ft| ft.is_file()) {
+                let path = entry
+                    .path()
+                    .strip_prefix(&args.path)
+                    .unwrap_or_else(|_| entry.path())
+                    .to_path_buf();
+                if !filter.matches(&path) {
+                    continue;
+                }
+                files.push(read::read(entry.path(), path, &read_options, estimator.as_ref()));
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    // 3. Fit the files into the token budgets
+    let budget = Budget {
+        max_tokens: args.max_tokens,
+        max_file_tokens: args.max_file_tokens,
+        priority: args.priority,
+    };
+    tokens::apply_budget(&mut files, &budget, estimator.as_ref());
+
+    // 4. Write the files that made it in, and the metadata of the skipped ones, to the output
+    // file in the chosen format
+    let written_files: Vec<&CollectedFile> = files
+        .iter()
+        .filter(|file| file.inclusion != Inclusion::Dropped)
+        .collect();
+
+    let file = fs::File::create(&output_file)
+        .with_context(|| format!("Failed to create output file: {:?}", &output_file))?;
+    let mut writer = BufWriter::new(file);
+    format::write(&written_files, args.format, &mut writer)?;
+    writer.flush().context("Failed to write the output file")?;
+
+    logger::info(&format!(
+        "Repository content successfully saved to {:?}",
+        &output_file
+    ));
+
+    // 5. Report how many tokens went in, and what had to give
+    let included: usize = files
+        .iter()
+        .filter(|file| file.has_content())
+        .map(|file| file.tokens)
+        .sum();
+    let truncated = files
+        .iter()
+        .filter(|file| matches!(file.inclusion, Inclusion::Truncated { .. }))
+        .count();
+    let dropped = files.iter().filter(|file| file.inclusion == Inclusion::Dropped).count();
+    let skipped = files.iter().filter(|file| file.skip_reason().is_some()).count();
+    logger::info(&format!(
+        "Collected {} files, about {} tokens ({} truncated, {} left out, {} skipped)",
+        files.len() - dropped - skipped,
+        included,
+        truncated,
+        dropped,
+        skipped
+    ));
+    if args.token_report {
+        tokens::print_report(&files);
+    }
+
+    Ok(())
+}
+
+/// Whether `--ignore`, `--ignore-all` or the output file rule excludes `path` or one of the
+/// directories it is in. The walker never descends into those, but git lists files directly.
+fn is_overridden(overrides: &Override, path: &Path) -> bool {
+    path.ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .enumerate()
+        .any(|(depth, ancestor)| overrides.matched(ancestor, depth > 0).is_ignore())
+}
@@ -0,0 +1,286 @@
+use crate::commands::git_utilities::collect::tokens::TokenEstimator;
+use crate::commands::git_utilities::utils::human_size;
+use std::fmt;
+use std::path::PathBuf;
+
+/// A file read by `collect`, and whether it made it into the output. Every output format is
+/// rendered from these.
+#[derive(Debug)]
+pub struct CollectedFile {
+    /// The path of the file, relative to the collected directory.
+    pub path: PathBuf,
+    /// The size of the file on disk, in bytes.
+    pub size: u64,
+    /// The MIME type sniffed from the content, or guessed from the extension.
+    pub mime_type: Option<&'static str>,
+    /// Empty for skipped files.
+    pub content: String,
+    /// The tokens of `content` and `diff`, as they will be written.
+    pub tokens: usize,
+    pub inclusion: Inclusion,
+    /// How the file changed, when collecting the files changed in git.
+    pub change: Option<Change>,
+    /// The unified diff of the change, when asked for with `--embed`.
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inclusion {
+    Whole,
+    /// Cut down to fit a budget. Holds the token count of the whole file.
+    Truncated { original_tokens: usize },
+    /// Left out because the total budget was spent.
+    Dropped,
+    /// Not read as text. Only the metadata of the file goes into the output.
+    Skipped(SkipReason),
+}
+
+/// How a file changed between the two sides of a git comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+    /// Moved from another path, and maybe modified too.
+    Renamed { from: PathBuf },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added => write!(f, "added"),
+            Change::Modified => write!(f, "modified"),
+            Change::Deleted => write!(f, "deleted"),
+            Change::Renamed { from } => write!(f, "renamed from {}", from.display()),
+        }
+    }
+}
+
+/// Why a file was not read as text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    Binary,
+    /// Larger than `--max-file-size`.
+    TooLarge { limit: u64 },
+    /// Not valid UTF-8 with the `--encoding` in use. Holds the encoding it was detected to be in,
+    /// if it was.
+    NotUtf8 { encoding: Option<&'static str> },
+    Unreadable(String),
+    /// A submodule, whose commit is all there is to it. Holds the commit after the change, if git
+    /// knows it.
+    Submodule { commit: Option<String> },
+    /// Staged with conflicts left to resolve, so there is no staged content.
+    Unmerged,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkipReason::Binary => write!(f, "binary"),
+            SkipReason::TooLarge { limit } => write!(f, "larger than {}", human_size(*limit)),
+            SkipReason::NotUtf8 { encoding: Some(encoding) } => write!(f, "encoded in {}", encoding),
+            SkipReason::NotUtf8 { encoding: None } => write!(f, "not valid UTF-8"),
+            SkipReason::Unreadable(err) => write!(f, "unreadable: {}", err),
+            SkipReason::Submodule { commit: Some(commit) } => write!(f, "submodule at {}", commit),
+            SkipReason::Submodule { commit: None } => write!(f, "submodule"),
+            SkipReason::Unmerged => write!(f, "unmerged"),
+        }
+    }
+}
+
+impl CollectedFile {
+    pub fn new(
+        path: PathBuf,
+        size: u64,
+        mime_type: Option<&'static str>,
+        content: String,
+        estimator: &dyn TokenEstimator,
+    ) -> Self {
+        Self {
+            tokens: estimator.count(&content),
+            path,
+            size,
+            mime_type,
+            content,
+            inclusion: Inclusion::Whole,
+            change: None,
+            diff: None,
+        }
+    }
+
+    pub fn skipped(path: PathBuf, size: u64, mime_type: Option<&'static str>, reason: SkipReason) -> Self {
+        Self {
+            path,
+            size,
+            mime_type,
+            content: String::new(),
+            tokens: 0,
+            inclusion: Inclusion::Skipped(reason),
+            change: None,
+            diff: None,
+        }
+    }
+
+    /// Records how the file changed. With `keep_content` false, the diff replaces the content.
+    pub fn attach_change(
+        &mut self,
+        change: Change,
+        diff: Option<String>,
+        keep_content: bool,
+        estimator: &dyn TokenEstimator,
+    ) {
+        if !keep_content {
+            self.content.clear();
+        }
+        self.change = Some(change);
+        self.diff = diff;
+        self.tokens = self.count_tokens(estimator);
+    }
+
+    /// Whether the content goes into the output. It doesn't when the diff replaces it, or when
+    /// nothing is left of a changed file.
+    pub fn shows_content(&self) -> bool {
+        !self.content.is_empty() || (self.diff.is_none() && self.change.is_none())
+    }
+
+    /// Whether the content of the file goes into the output, whole or truncated.
+    pub fn has_content(&self) -> bool {
+        matches!(self.inclusion, Inclusion::Whole | Inclusion::Truncated { .. })
+    }
+
+    /// Why the file was skipped, if it was.
+    pub fn skip_reason(&self) -> Option<&SkipReason> {
+        match &self.inclusion {
+            Inclusion::Skipped(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// Cuts the content down to `max_tokens`, the note saying so included, remembering how long
+    /// it was. The diff is kept whole if it fits, as it is what matters most about a changed file.
+    pub fn truncate(&mut self, max_tokens: usize, estimator: &dyn TokenEstimator) {
+        // A file cut to --max-file-tokens first, then to the rest of --max-tokens, was still this long
+        let original_tokens = match self.inclusion {
+            Inclusion::Truncated { original_tokens } => original_tokens,
+            _ => self.tokens,
+        };
+        // The note can't be longer than with `max_tokens` in it, as the content will be shorter
+        let max_tokens = max_tokens.saturating_sub(estimator.count(&truncation_note(max_tokens, original_tokens)));
+
+        let diff_tokens = self.diff.as_deref().map_or(0, |diff| estimator.count(diff));
+        if diff_tokens > max_tokens {
+            self.content.clear();
+            self.diff = self.diff.as_deref().map(|diff| cut(diff, max_tokens, estimator));
+        } else {
+            self.content = cut(&self.content, max_tokens - diff_tokens, estimator);
+        }
+
+        self.inclusion = Inclusion::Truncated { original_tokens };
+        self.tokens = self.count_tokens(estimator);
+    }
+
+    /// The tokens the file takes up in the output: those of its content and diff, and of the
+    /// truncation note if it has one.
+    pub fn output_tokens(&self, estimator: &dyn TokenEstimator) -> usize {
+        self.tokens + self.note().map_or(0, |note| estimator.count(&note))
+    }
+
+    fn count_tokens(&self, estimator: &dyn TokenEstimator) -> usize {
+        estimator.count(&self.content) + self.diff.as_deref().map_or(0, |diff| estimator.count(diff))
+    }
+
+    /// A line telling the reader that the content is not all there, if it isn't.
+    pub fn note(&self) -> Option<String> {
+        match self.inclusion {
+            Inclusion::Truncated { original_tokens } => Some(truncation_note(self.tokens, original_tokens)),
+            _ => None,
+        }
+    }
+
+    /// The language of the file for syntax highlighting, e.g. `rust`, from its name.
+    pub fn language(&self) -> Option<&'static str> {
+        let name = self.path.file_name()?.to_str()?;
+        match name {
+            "Dockerfile" => return Some("dockerfile"),
+            "Makefile" | "makefile" | "GNUmakefile" => return Some("makefile"),
+            "CMakeLists.txt" => return Some("cmake"),
+            _ => {}
+        }
+
+        let extension = self.path.extension()?.to_str()?.to_lowercase();
+        let language = match extension.as_str() {
+            "rs" => "rust",
+            "toml" => "toml",
+            "py" | "pyi" => "python",
+            "js" | "mjs" | "cjs" => "javascript",
+            "jsx" => "jsx",
+            "ts" | "mts" | "cts" => "typescript",
+            "tsx" => "tsx",
+            "go" => "go",
+            "java" => "java",
+            "kt" | "kts" => "kotlin",
+            "scala" => "scala",
+            "c" | "h" => "c",
+            "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => "cpp",
+            "cs" => "csharp",
+            "fs" | "fsx" => "fsharp",
+            "rb" => "ruby",
+            "php" => "php",
+            "swift" => "swift",
+            "m" | "mm" => "objectivec",
+            "dart" => "dart",
+            "lua" => "lua",
+            "r" => "r",
+            "pl" | "pm" => "perl",
+            "hs" => "haskell",
+            "ex" | "exs" => "elixir",
+            "erl" | "hrl" => "erlang",
+            "clj" | "cljs" | "edn" => "clojure",
+            "ml" | "mli" => "ocaml",
+            "zig" => "zig",
+            "sh" | "bash" => "bash",
+            "zsh" => "zsh",
+            "fish" => "fish",
+            "ps1" | "psm1" => "powershell",
+            "bat" | "cmd" => "batch",
+            "sql" => "sql",
+            "html" | "htm" => "html",
+            "css" => "css",
+            "scss" => "scss",
+            "sass" => "sass",
+            "less" => "less",
+            "vue" => "vue",
+            "svelte" => "svelte",
+            "json" | "jsonc" => "json",
+            "yaml" | "yml" => "yaml",
+            "xml" | "xsd" | "xsl" | "svg" => "xml",
+            "md" | "markdown" => "markdown",
+            "rst" => "rst",
+            "tex" => "latex",
+            "ini" | "cfg" => "ini",
+            "graphql" | "gql" => "graphql",
+            "proto" => "protobuf",
+            "tf" | "hcl" => "hcl",
+            "nix" => "nix",
+            "diff" | "patch" => "diff",
+            "gradle" => "groovy",
+            "groovy" => "groovy",
+            _ => return None,
+        };
+        Some(language)
+    }
+}
+
+fn truncation_note(tokens: usize, original_tokens: usize) -> String {
+    format!("[... truncated to {} of {} tokens]", tokens, original_tokens)
+}
+
+/// The start of `text` that fits in `max_tokens`, ending on a whole line unless that would leave
+/// next to nothing.
+fn cut(text: &str, max_tokens: usize, estimator: &dyn TokenEstimator) -> String {
+    let mut text = estimator.truncate(text, max_tokens);
+    if let Some(end) = text.rfind('\n').filter(|end| *end > text.len() / 2) {
+        text.truncate(end + 1);
+    }
+    text
+}
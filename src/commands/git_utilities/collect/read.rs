@@ -0,0 +1,222 @@
+use crate::cli::TextEncoding;
+use crate::commands::git_utilities::collect::model::{CollectedFile, SkipReason};
+use crate::commands::git_utilities::collect::tokens::TokenEstimator;
+use content_inspector::ContentType;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use infer::MatcherType;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How `collect` decides which files to read as text, and how.
+pub struct ReadOptions {
+    /// Files larger than this are skipped without being read. `0` for no limit.
+    pub max_file_size: u64,
+    pub encoding: TextEncoding,
+}
+
+/// Bytes looked at to tell UTF-16 without a byte order mark from binary data.
+const SNIFF_LEN: usize = 1024;
+
+/// Reads the file at `full_path` as text, or records why it wasn't. `path` is the path the file
+/// gets in the output.
+pub fn read(full_path: &Path, path: PathBuf, options: &ReadOptions, estimator: &dyn TokenEstimator) -> CollectedFile {
+    let guessed_mime_type = mime_guess::from_path(&path).first_raw();
+
+    // Skip files that are too large before reading them
+    let size = match fs::metadata(full_path) {
+        Ok(metadata) => metadata.len(),
+        Err(err) => return CollectedFile::skipped(path, 0, guessed_mime_type, SkipReason::Unreadable(err.to_string())),
+    };
+    if options.max_file_size > 0 && size > options.max_file_size {
+        let reason = SkipReason::TooLarge {
+            limit: options.max_file_size,
+        };
+        return CollectedFile::skipped(path, size, guessed_mime_type, reason);
+    }
+
+    match fs::read(full_path) {
+        Ok(bytes) => from_bytes(path, bytes, options, estimator),
+        Err(err) => CollectedFile::skipped(path, size, guessed_mime_type, SkipReason::Unreadable(err.to_string())),
+    }
+}
+
+/// Reads the content of a file already loaded into memory, e.g. from a git object, as text, or
+/// records why it wasn't.
+pub fn from_bytes(path: PathBuf, bytes: Vec<u8>, options: &ReadOptions, estimator: &dyn TokenEstimator) -> CollectedFile {
+    let size = bytes.len() as u64;
+    let mime_type = infer::get(&bytes)
+        .map(|kind| kind.mime_type())
+        .or_else(|| mime_guess::from_path(&path).first_raw());
+    if options.max_file_size > 0 && size > options.max_file_size {
+        let reason = SkipReason::TooLarge {
+            limit: options.max_file_size,
+        };
+        return CollectedFile::skipped(path, size, mime_type, reason);
+    }
+
+    match text(bytes, options.encoding) {
+        Ok(content) => CollectedFile::new(path, size, mime_type, content, estimator),
+        Err(reason) => CollectedFile::skipped(path, size, mime_type, reason),
+    }
+}
+
+/// Decodes `bytes` as text with `encoding`, unless they are binary.
+pub fn text(bytes: Vec<u8>, encoding: TextEncoding) -> Result<String, SkipReason> {
+    // Formats like PDF have no NUL bytes early on, so a known binary signature counts too,
+    // unless the content is valid UTF-8 anyway
+    let has_binary_signature = infer::get(&bytes).is_some_and(|kind| kind.matcher_type() != MatcherType::Text);
+    if has_binary_signature && std::str::from_utf8(&bytes).is_err() {
+        return Err(SkipReason::Binary);
+    }
+
+    decode(bytes, encoding)
+}
+
+fn decode(mut bytes: Vec<u8>, encoding: TextEncoding) -> Result<String, SkipReason> {
+    match content_inspector::inspect(&bytes) {
+        ContentType::BINARY => match bomless_utf16(&bytes) {
+            Some(utf16) if encoding == TextEncoding::Detect => Ok(decode_with(utf16, &bytes)),
+            Some(utf16) => Err(SkipReason::NotUtf8 {
+                encoding: Some(utf16.name()),
+            }),
+            None => Err(SkipReason::Binary),
+        },
+        ContentType::UTF_16LE | ContentType::UTF_16BE => {
+            let utf16 = if bytes.starts_with(b"\xFF\xFE") { UTF_16LE } else { UTF_16BE };
+            if encoding == TextEncoding::Detect {
+                Ok(decode_with(utf16, &bytes))
+            } else {
+                Err(SkipReason::NotUtf8 {
+                    encoding: Some(utf16.name()),
+                })
+            }
+        }
+        // Rare enough that encoding_rs doesn't support it
+        ContentType::UTF_32LE => Err(SkipReason::NotUtf8 {
+            encoding: Some("UTF-32LE"),
+        }),
+        ContentType::UTF_32BE => Err(SkipReason::NotUtf8 {
+            encoding: Some("UTF-32BE"),
+        }),
+        ContentType::UTF_8 | ContentType::UTF_8_BOM => {
+            if bytes.starts_with(b"\xEF\xBB\xBF") {
+                bytes.drain(..3);
+            }
+            match String::from_utf8(bytes) {
+                Ok(content) => Ok(content),
+                Err(err) => match encoding {
+                    TextEncoding::Utf8 => Err(SkipReason::NotUtf8 { encoding: None }),
+                    TextEncoding::Lossy => Ok(String::from_utf8_lossy(err.as_bytes()).into_owned()),
+                    // Every byte is a character in Windows-1252, the superset of Latin-1 that
+                    // files labelled Latin-1 are usually in
+                    TextEncoding::Detect => Ok(decode_with(WINDOWS_1252, err.as_bytes())),
+                },
+            }
+        }
+    }
+}
+
+/// Decodes `bytes` with `encoding`, dropping a byte order mark and replacing invalid sequences.
+fn decode_with(encoding: &'static Encoding, bytes: &[u8]) -> String {
+    encoding.decode_with_bom_removal(bytes).0.into_owned()
+}
+
+/// Text in UTF-16 without a byte order mark looks binary, as every ASCII character comes with a
+/// NUL byte. It is told apart by those NULs all being on the same side of each pair of bytes, and
+/// by decoding to text without control characters other than whitespace.
+fn bomless_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let sample = &bytes[..bytes.len().min(SNIFF_LEN)];
+    let pairs = sample.len() / 2;
+    let nuls_at = |offset: usize| sample.iter().skip(offset).step_by(2).filter(|byte| **byte == 0).count();
+    let (even, odd) = (nuls_at(0), nuls_at(1));
+
+    let encoding = if even == 0 && odd * 2 >= pairs {
+        UTF_16LE
+    } else if odd == 0 && even * 2 >= pairs {
+        UTF_16BE
+    } else {
+        return None;
+    };
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .is_some_and(|text| !text.chars().any(|c| c.is_control() && !c.is_whitespace()))
+        .then_some(encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| if encoding == UTF_16LE { unit.to_le_bytes() } else { unit.to_be_bytes() })
+            .collect()
+    }
+
+    #[test]
+    fn tells_bomless_utf16_by_the_side_of_its_nuls() {
+        assert_eq!(bomless_utf16(&utf16("fn main() {}\n", UTF_16LE)), Some(UTF_16LE));
+        assert_eq!(bomless_utf16(&utf16("fn main() {}\n", UTF_16BE)), Some(UTF_16BE));
+        // Mostly non-ASCII text has fewer NULs, but still all on one side
+        assert_eq!(bomless_utf16(&utf16("ab Ünïcödé", UTF_16LE)), Some(UTF_16LE));
+    }
+
+    #[test]
+    fn doesnt_take_binary_data_for_bomless_utf16() {
+        assert_eq!(bomless_utf16(b""), None);
+        // UTF-16 has an even length
+        assert_eq!(bomless_utf16(b"a\0b\0c"), None);
+        // NULs on both sides
+        assert_eq!(bomless_utf16(b"\0\0\0\x01\x02\0\0\0"), None);
+        // Too few NULs
+        assert_eq!(bomless_utf16(b"\x89PNG\r\n\x1a\0"), None);
+        // An unpaired surrogate
+        assert_eq!(bomless_utf16(&[0x00, 0xD8, b'a', 0x00]), None);
+    }
+
+    #[test]
+    fn decodes_utf16_only_when_detecting() {
+        let mut with_bom = b"\xFF\xFE".to_vec();
+        with_bom.extend(utf16("héllo", UTF_16LE));
+        assert_eq!(decode(with_bom.clone(), TextEncoding::Detect), Ok("héllo".to_string()));
+        assert_eq!(
+            decode(with_bom, TextEncoding::Utf8),
+            Err(SkipReason::NotUtf8 {
+                encoding: Some("UTF-16LE")
+            })
+        );
+
+        let bomless = utf16("hello", UTF_16BE);
+        assert_eq!(decode(bomless.clone(), TextEncoding::Detect), Ok("hello".to_string()));
+        assert_eq!(
+            decode(bomless, TextEncoding::Lossy),
+            Err(SkipReason::NotUtf8 {
+                encoding: Some("UTF-16BE")
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_invalid_utf8_by_the_chosen_encoding() {
+        let latin1 = b"caf\xE9 \x80".to_vec();
+        assert_eq!(decode(latin1.clone(), TextEncoding::Detect), Ok("café €".to_string()));
+        assert_eq!(decode(latin1.clone(), TextEncoding::Lossy), Ok("caf\u{FFFD} \u{FFFD}".to_string()));
+        assert_eq!(decode(latin1, TextEncoding::Utf8), Err(SkipReason::NotUtf8 { encoding: None }));
+    }
+
+    #[test]
+    fn drops_the_utf8_byte_order_mark() {
+        assert_eq!(decode(b"\xEF\xBB\xBFhi".to_vec(), TextEncoding::Utf8), Ok("hi".to_string()));
+    }
+
+    #[test]
+    fn skips_binary_files() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        assert_eq!(text(png, TextEncoding::Detect), Err(SkipReason::Binary));
+        assert_eq!(text(b"\0\x01\x02\x03\0\x05".to_vec(), TextEncoding::Lossy), Err(SkipReason::Binary));
+    }
+}
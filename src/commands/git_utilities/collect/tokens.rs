@@ -0,0 +1,300 @@
+use crate::cli::{Priority, Tokenizer};
+use crate::commands::git_utilities::collect::model::{CollectedFile, Inclusion};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Below this many tokens, the end of the budget is not worth a truncated file.
+const MIN_TRUNCATED_TOKENS: usize = 100;
+
+/// Counts tokens the way a model would see them, more or less precisely.
+pub trait TokenEstimator {
+    fn count(&self, text: &str) -> usize;
+
+    /// The start of `text` that fits in `max_tokens`.
+    fn truncate(&self, text: &str, max_tokens: usize) -> String;
+}
+
+/// Creates the estimator selected on the command line.
+pub fn estimator(tokenizer: Tokenizer) -> Box<dyn TokenEstimator> {
+    match tokenizer {
+        Tokenizer::Chars => Box::new(CharEstimator),
+        #[cfg(feature = "tiktoken")]
+        Tokenizer::Cl100k => Box::new(BpeEstimator(tiktoken_rs::cl100k_base_singleton())),
+        #[cfg(feature = "tiktoken")]
+        Tokenizer::O200k => Box::new(BpeEstimator(tiktoken_rs::o200k_base_singleton())),
+    }
+}
+
+/// The usual rule of thumb of four characters per token. Fast, and close enough for English
+/// text and code.
+struct CharEstimator;
+
+const CHARS_PER_TOKEN: usize = 4;
+
+impl TokenEstimator for CharEstimator {
+    fn count(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(CHARS_PER_TOKEN)
+    }
+
+    fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        match text.char_indices().nth(max_tokens * CHARS_PER_TOKEN) {
+            Some((end, _)) => text[..end].to_string(),
+            None => text.to_string(),
+        }
+    }
+}
+
+/// Exact counts with one of OpenAI's byte-pair encodings.
+#[cfg(feature = "tiktoken")]
+struct BpeEstimator(&'static tiktoken_rs::CoreBPE);
+
+#[cfg(feature = "tiktoken")]
+impl TokenEstimator for BpeEstimator {
+    fn count(&self, text: &str) -> usize {
+        self.0.encode_ordinary(text).len()
+    }
+
+    fn truncate(&self, text: &str, max_tokens: usize) -> String {
+        let tokens = self.0.encode_ordinary(text);
+        if tokens.len() <= max_tokens {
+            return text.to_string();
+        }
+
+        // The last token may end in the middle of a character, which takes up to 4 tokens to finish
+        (max_tokens.saturating_sub(3)..=max_tokens)
+            .rev()
+            .find_map(|end| self.0.decode(tokens[..end].to_vec()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// The limits `--max-tokens` and `--max-file-tokens` set.
+pub struct Budget {
+    pub max_tokens: Option<usize>,
+    pub max_file_tokens: Option<usize>,
+    pub priority: Priority,
+}
+
+/// Truncates the files longer than `max_file_tokens`, then fits the files into `max_tokens` in
+/// the order of `priority`. A file that doesn't fit whole is truncated to what is left of the
+/// budget if enough is, and dropped otherwise. Truncation notes count towards both limits.
+pub fn apply_budget(files: &mut [CollectedFile], budget: &Budget, estimator: &dyn TokenEstimator) {
+    if let Some(max_file_tokens) = budget.max_file_tokens {
+        for file in files.iter_mut().filter(|file| file.tokens > max_file_tokens) {
+            file.truncate(max_file_tokens, estimator);
+        }
+    }
+
+    let Some(max_tokens) = budget.max_tokens else {
+        return;
+    };
+
+    let mut order: Vec<usize> = (0..files.len()).collect();
+    match budget.priority {
+        Priority::SourceFirst => order.sort_by_key(|index| (is_test(&files[*index].path), files[*index].tokens)),
+        Priority::Smallest => order.sort_by_key(|index| files[*index].tokens),
+        Priority::Path => {}
+    }
+
+    let mut remaining = max_tokens;
+    for index in order {
+        let file = &mut files[index];
+        if !file.has_content() {
+            continue;
+        } else if file.output_tokens(estimator) <= remaining {
+            remaining -= file.output_tokens(estimator);
+        } else if remaining >= MIN_TRUNCATED_TOKENS {
+            file.truncate(remaining, estimator);
+            remaining = remaining.saturating_sub(file.output_tokens(estimator));
+        } else {
+            file.inclusion = Inclusion::Dropped;
+        }
+    }
+}
+
+/// Whether the file looks like a test, from the usual naming conventions.
+fn is_test(path: &Path) -> bool {
+    let in_test_dir = path.parent().is_some_and(|parent| {
+        parent.components().any(|component| match component {
+            Component::Normal(name) => {
+                matches!(name.to_str(), Some("test" | "tests" | "spec" | "specs" | "__tests__" | "testdata"))
+            }
+            _ => false,
+        })
+    });
+
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+    let is_test_file = stem.starts_with("test_")
+        || ["_test", "_spec", ".test", ".spec", "Test", "Tests"]
+            .iter()
+            .any(|suffix| stem.ends_with(suffix));
+
+    in_test_dir || is_test_file
+}
+
+/// Prints the tokens of every file that made it into the output, then of every directory,
+/// largest first.
+pub fn print_report(files: &[CollectedFile]) {
+    let mut directories: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    let mut rows: Vec<(usize, String)> = Vec::new();
+
+    for file in files {
+        let detail = match &file.inclusion {
+            Inclusion::Whole => String::new(),
+            Inclusion::Truncated { original_tokens } => format!(" (truncated from {})", original_tokens),
+            Inclusion::Dropped => format!(" (dropped, {} tokens)", file.tokens),
+            Inclusion::Skipped(reason) => format!(" (skipped, {})", reason),
+        };
+        let tokens = if file.has_content() { file.tokens } else { 0 };
+        rows.push((tokens, format!("{}{}", file.path.display(), detail)));
+
+        for directory in file.path.ancestors().skip(1) {
+            *directories.entry(directory.to_path_buf()).or_default() += tokens;
+        }
+    }
+
+    print_table("FILE", rows);
+
+    let rows = directories
+        .into_iter()
+        .map(|(directory, tokens)| {
+            let name = if directory.as_os_str().is_empty() {
+                ".".to_string()
+            } else {
+                format!("{}/", directory.display())
+            };
+            (tokens, name)
+        })
+        .collect();
+    println!();
+    print_table("DIRECTORY", rows);
+}
+
+fn print_table(heading: &str, mut rows: Vec<(usize, String)>) {
+    rows.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    let width = rows
+        .iter()
+        .map(|(tokens, _)| tokens.to_string().len())
+        .chain(["TOKENS".len()])
+        .max()
+        .unwrap_or_default();
+
+    println!("{:>width$}  {}", "TOKENS", heading, width = width);
+    for (tokens, name) in rows {
+        println!("{:>width$}  {}", tokens, name, width = width);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file of `lines` lines of 39 characters and a newline, i.e. 10 tokens each.
+    fn file(path: &str, lines: usize) -> CollectedFile {
+        let content = format!("{}\n", "x".repeat(39)).repeat(lines);
+        CollectedFile::new(PathBuf::from(path), content.len() as u64, None, content, &CharEstimator)
+    }
+
+    fn budget(max_tokens: Option<usize>, max_file_tokens: Option<usize>, priority: Priority) -> Budget {
+        Budget {
+            max_tokens,
+            max_file_tokens,
+            priority,
+        }
+    }
+
+    /// The tokens the files take up in the output, with the truncation notes.
+    fn output_tokens(files: &[CollectedFile]) -> usize {
+        files
+            .iter()
+            .filter(|file| file.has_content())
+            .map(|file| file.output_tokens(&CharEstimator))
+            .sum()
+    }
+
+    #[test]
+    fn files_within_the_budget_are_kept_whole() {
+        let mut files = vec![file("a.rs", 10), file("b.rs", 20)];
+        apply_budget(&mut files, &budget(Some(300), None, Priority::Path), &CharEstimator);
+
+        assert!(files.iter().all(|file| file.inclusion == Inclusion::Whole));
+        assert_eq!(output_tokens(&files), 300);
+    }
+
+    #[test]
+    fn max_tokens_is_a_hard_cap_with_the_note() {
+        let mut files = vec![file("a.rs", 30), file("b.rs", 30)];
+        apply_budget(&mut files, &budget(Some(500), None, Priority::Path), &CharEstimator);
+
+        assert_eq!(files[0].inclusion, Inclusion::Whole);
+        assert_eq!(files[1].inclusion, Inclusion::Truncated { original_tokens: 300 });
+        assert!(output_tokens(&files) <= 500, "{} tokens", output_tokens(&files));
+        assert!(files[1].note().unwrap().ends_with("of 300 tokens]"));
+    }
+
+    #[test]
+    fn truncating_twice_keeps_the_original_count() {
+        let mut files = vec![file("a.rs", 30), file("b.rs", 50)];
+        apply_budget(&mut files, &budget(Some(500), Some(400), Priority::Path), &CharEstimator);
+
+        assert_eq!(files[1].inclusion, Inclusion::Truncated { original_tokens: 500 });
+        assert!(files[1].output_tokens(&CharEstimator) <= 200);
+        assert!(output_tokens(&files) <= 500, "{} tokens", output_tokens(&files));
+    }
+
+    #[test]
+    fn max_file_tokens_counts_the_note() {
+        let mut files = vec![file("a.rs", 100)];
+        apply_budget(&mut files, &budget(None, Some(250), Priority::Path), &CharEstimator);
+
+        assert_eq!(files[0].inclusion, Inclusion::Truncated { original_tokens: 1000 });
+        assert!(files[0].output_tokens(&CharEstimator) <= 250);
+    }
+
+    #[test]
+    fn files_that_dont_fit_the_rest_are_dropped() {
+        let mut files = vec![file("a.rs", 25), file("b.rs", 10)];
+        apply_budget(&mut files, &budget(Some(300), None, Priority::Path), &CharEstimator);
+
+        assert_eq!(files[0].inclusion, Inclusion::Whole);
+        assert_eq!(files[1].inclusion, Inclusion::Dropped);
+    }
+
+    #[test]
+    fn priorities() {
+        let inclusions = |priority: Priority| {
+            let mut files = vec![file("src/big.rs", 30), file("src/small.rs", 10), file("tests/it.rs", 5)];
+            apply_budget(&mut files, &budget(Some(400), None, priority), &CharEstimator);
+            files.into_iter().map(|file| file.inclusion).collect::<Vec<_>>()
+        };
+
+        // Tests go in last, so there is nothing left for them
+        assert_eq!(
+            inclusions(Priority::SourceFirst),
+            [Inclusion::Whole, Inclusion::Whole, Inclusion::Dropped]
+        );
+        // The smallest files go in first, and the largest is cut to the rest
+        assert_eq!(
+            inclusions(Priority::Smallest),
+            [
+                Inclusion::Truncated { original_tokens: 300 },
+                Inclusion::Whole,
+                Inclusion::Whole
+            ]
+        );
+        assert_eq!(
+            inclusions(Priority::Path),
+            [Inclusion::Whole, Inclusion::Whole, Inclusion::Dropped]
+        );
+    }
+
+    #[test]
+    fn recognizes_tests() {
+        for path in ["tests/api.rs", "src/__tests__/app.js", "test_utils.py", "user_test.go", "UserTest.java", "a.spec.ts"] {
+            assert!(is_test(Path::new(path)), "{}", path);
+        }
+        for path in ["src/main.rs", "src/contest.rs", "latest/notes.md"] {
+            assert!(!is_test(Path::new(path)), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+use crate::cli::CommitReposArgs;
+use crate::commands::git_utilities::backend::{self, ChangeSelection, GitBackend};
+use crate::commands::git_utilities::report::ActionResult;
+use crate::commands::git_utilities::status::RepoStatus;
+use crate::commands::git_utilities::utils::run_with_previewed_action;
+use crate::utils::logger;
+
+pub fn run(args: &CommitReposArgs) -> Result<(), anyhow::Error> {
+    let backend = backend::create(args.remote.backend);
+    let selection = ChangeSelection {
+        staged_only: !args.all && args.paths.is_empty(),
+        untracked: true,
+        paths: args.paths.clone(),
+    };
+
+    let preview = |path: &Path| backend.diffstat(path, &selection);
+
+    run_with_previewed_action(
+        &args.remote,
+        "Commit these changes?",
+        args.yes,
+        &preview,
+        |path, status| is_eligible(backend.as_ref(), &selection, path, status),
+        |path, _status| {
+            if args.remote.dry_run {
+                plan_commit(backend.as_ref(), args, &selection, path)
+            } else {
+                on_ok_commit(backend.as_ref(), args, &selection, path)
+            }
+        },
+    )
+}
+
+/// Whether there is something to commit, on a branch, with no conflict or operation in progress.
+fn is_eligible(backend: &dyn GitBackend, selection: &ChangeSelection, path: &Path, status: &RepoStatus) -> bool {
+    if status.is_detached() || status.operation.is_some() || !status.conflicted.is_empty() {
+        return false;
+    }
+
+    let has_changes = if selection.staged_only {
+        !status.staged.is_empty()
+    } else {
+        status.has_tracked_changes() || !status.untracked.is_empty()
+    };
+    // The status covers the whole repository, and `--paths` may leave all of its changes out.
+    // If git can't tell, the commit is left to report why
+    has_changes
+        && (selection.paths.is_empty() || backend.has_changes(path, selection).unwrap_or(true))
+}
+
+fn on_ok_commit(
+    backend: &dyn GitBackend,
+    args: &CommitReposArgs,
+    selection: &ChangeSelection,
+    path: PathBuf,
+) -> anyhow::Result<ActionResult, anyhow::Error> {
+    logger::debug(&format!("Committing in '{}'... ", path.display()));
+
+    backend.commit(&path, &args.message, selection).inspect_err(|err| {
+        logger::error(&format!("Failed to commit in '{}': {:#}", path.display(), err));
+    })?;
+
+    Ok(ActionResult::Succeeded)
+}
+
+/// Describes the commit `on_ok_commit` would make, and shows the changes it would contain.
+fn plan_commit(
+    backend: &dyn GitBackend,
+    args: &CommitReposArgs,
+    selection: &ChangeSelection,
+    path: PathBuf,
+) -> anyhow::Result<ActionResult, anyhow::Error> {
+    for line in backend.diffstat(&path, selection)? {
+        logger::output(&format!("{}: {}", path.display(), line));
+    }
+
+    let pathspec = if args.paths.is_empty() {
+        String::new()
+    } else {
+        format!(" -- {}", args.paths.join(" "))
+    };
+    let mut command = String::new();
+    if !selection.staged_only {
+        command.push_str(&format!("git -C {} add --all{} && ", path.display(), pathspec));
+    }
+    command.push_str(&format!("git -C {} commit --message {:?}{}", path.display(), args.message, pathspec));
+
+    Ok(ActionResult::DryRun {
+        command,
+        commits: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::RemoteOperationArgs;
+    use crate::commands::git_utilities::test_support::{git, write, Fixture};
+
+    #[test]
+    fn commits_only_the_repositories_with_changes_to_the_paths() {
+        let fixture = Fixture::new();
+        let a = fixture.repo("a");
+        let b = fixture.repo("b");
+        write(&a, "foo.txt", "foo\n");
+        write(&b, "bar.txt", "bar\n");
+
+        let args = CommitReposArgs {
+            remote: RemoteOperationArgs {
+                directories: vec![fixture.path().to_path_buf()],
+                ..RemoteOperationArgs::default()
+            },
+            message: "Add foo".to_string(),
+            all: false,
+            paths: vec!["foo.txt".to_string()],
+            yes: true,
+        };
+        run(&args).unwrap();
+
+        assert_eq!(git(&a, &["log", "-1", "--format=%s"]), "Add foo");
+        assert_eq!(git(&b, &["log", "-1", "--format=%s"]), "Initial commit");
+        assert_eq!(git(&b, &["status", "--porcelain"]), "?? bar.txt");
+    }
+}
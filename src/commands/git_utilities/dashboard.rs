@@ -0,0 +1,500 @@
+use crate::cli::{PullReposArgs, PullStrategy, PushReposArgs, RemoteOperationArgs};
+use crate::commands::git_utilities::backend::cli::has_head;
+use crate::commands::git_utilities::backend::{self, CommitInfo, GitBackend, PullOptions, PushOptions};
+use crate::commands::git_utilities::filters::RepoFilter;
+use crate::commands::git_utilities::{process, pull_repos, push_repos};
+use crate::commands::git_utilities::report::RepoRecord;
+use crate::commands::git_utilities::status::{Condition, RepoStatus};
+use crate::commands::git_utilities::utils::{collect_statuses, parallel_map, relative_time};
+use crate::utils::logger;
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Cell, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HELP: &str =
+    "↑/↓ move  s sort  S reverse  / filter  p pull  P push  d diff  Enter shell  r refresh  q quit";
+
+/// A repository as shown in the dashboard.
+struct Entry {
+    record: RepoRecord,
+    last_commit: Option<CommitInfo>,
+}
+
+impl Entry {
+    /// The number of files with staged, unstaged, untracked or conflicting changes.
+    fn changes(&self) -> usize {
+        self.record.status.as_ref().map_or(0, |status| {
+            status.staged.len() + status.modified.len() + status.untracked.len() + status.conflicted.len()
+        })
+    }
+}
+
+/// The columns the dashboard can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Path,
+    Branch,
+    Ahead,
+    Behind,
+    Changes,
+    LastCommit,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 6] = [
+        SortKey::Path,
+        SortKey::Branch,
+        SortKey::Ahead,
+        SortKey::Behind,
+        SortKey::Changes,
+        SortKey::LastCommit,
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            SortKey::Path => "Repository",
+            SortKey::Branch => "Branch",
+            SortKey::Ahead => "Ahead",
+            SortKey::Behind => "Behind",
+            SortKey::Changes => "Changes",
+            SortKey::LastCommit => "Last commit",
+        }
+    }
+
+    fn next(self) -> Self {
+        let index = SortKey::ALL.iter().position(|key| *key == self).unwrap_or(0);
+        SortKey::ALL[(index + 1) % SortKey::ALL.len()]
+    }
+}
+
+/// The state of the interactive `check-repos --tui` dashboard.
+struct Dashboard<'a> {
+    args: &'a RemoteOperationArgs,
+    backend: Box<dyn GitBackend>,
+    /// Built once from `args.filters`, as it may load the manifest.
+    repo_filter: RepoFilter,
+    /// The plain `pull-repos` and `push-repos` with the same `args`, whose rules the pull and
+    /// push actions follow.
+    pull_args: PullReposArgs,
+    push_args: PushReposArgs,
+    entries: Vec<Entry>,
+    /// Indices into `entries` of the rows currently shown, in display order.
+    visible: Vec<usize>,
+    sort_key: SortKey,
+    descending: bool,
+    filter: String,
+    editing_filter: bool,
+    table: TableState,
+    /// The outcome of the last action, shown above the key bindings.
+    message: String,
+}
+
+/// Opens the dashboard on the repositories inside `args.directories`, until the user quits.
+pub fn run(args: &RemoteOperationArgs) -> Result<()> {
+    let mut dashboard = Dashboard {
+        args,
+        backend: backend::create(args.backend),
+        repo_filter: RepoFilter::new(&args.filters)?,
+        pull_args: PullReposArgs {
+            remote: args.clone(),
+            strategy: PullStrategy::FfOnly,
+            autostash: false,
+            update_submodules: false,
+        },
+        push_args: PushReposArgs {
+            remote: args.clone(),
+            set_upstream: false,
+            tags: false,
+            force_with_lease: false,
+            remote_name: None,
+            yes: false,
+        },
+        entries: Vec::new(),
+        visible: Vec::new(),
+        sort_key: SortKey::Path,
+        descending: false,
+        filter: String::new(),
+        editing_filter: false,
+        table: TableState::default().with_selected(Some(0)),
+        message: String::new(),
+    };
+
+    // Load before taking over the terminal, so discovery warnings and errors stay readable
+    dashboard.refresh()?;
+
+    // Log lines would be drawn over the dashboard, and its table shows the errors anyway.
+    // Neither can git prompt for credentials while it owns the terminal.
+    logger::silence(true);
+    process::init_non_interactive(true);
+    let mut terminal = ratatui::init();
+    let result = dashboard.run(&mut terminal);
+    ratatui::restore();
+    logger::silence(false);
+
+    result
+}
+
+impl Dashboard<'_> {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if self.editing_filter {
+                match key.code {
+                    KeyCode::Enter => self.editing_filter = false,
+                    KeyCode::Esc => {
+                        self.editing_filter = false;
+                        self.filter.clear();
+                    }
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                    }
+                    KeyCode::Char(c) => self.filter.push(c),
+                    _ => {}
+                }
+                self.update_view();
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                KeyCode::Char('s') => {
+                    self.sort_key = self.sort_key.next();
+                    self.update_view();
+                }
+                KeyCode::Char('S') => {
+                    self.descending = !self.descending;
+                    self.update_view();
+                }
+                KeyCode::Char('/') => self.editing_filter = true,
+                KeyCode::Char('r') => {
+                    self.message = "Refreshing...".to_string();
+                    terminal.draw(|frame| self.draw(frame))?;
+                    self.refresh()?;
+                    self.message = "Refreshed.".to_string();
+                }
+                KeyCode::Char('p') => self.pull(terminal)?,
+                KeyCode::Char('P') => self.push(terminal)?,
+                KeyCode::Char('d') => self.diff(terminal)?,
+                KeyCode::Enter => self.shell(terminal)?,
+                _ => {}
+            }
+        }
+    }
+
+    /// Checks every repository again, keeping the current sort order and filter.
+    fn refresh(&mut self) -> Result<()> {
+        let records = collect_statuses(self.args, &self.repo_filter, self.backend.as_ref())?;
+        let last_commits = parallel_map(self.args.jobs, &records, |record| {
+            self.backend.last_commit(&record.path).ok().flatten()
+        });
+
+        self.entries = records
+            .into_iter()
+            .zip(last_commits)
+            .map(|(record, last_commit)| Entry { record, last_commit })
+            .collect();
+        self.update_view();
+        Ok(())
+    }
+
+    /// Checks a single repository again, after an action changed it.
+    fn refresh_entry(&mut self, index: usize) {
+        let entry = &mut self.entries[index];
+        if let Ok(Some(status)) = self.backend.status(&entry.record.path) {
+            entry.record.conditions = status.conditions();
+            entry.record.status = Some(status);
+        }
+        entry.last_commit = self.backend.last_commit(&entry.record.path).ok().flatten();
+        self.update_view();
+    }
+
+    /// Recomputes which entries are shown, and in which order.
+    fn update_view(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.visible = (0..self.entries.len())
+            .filter(|index| {
+                let record = &self.entries[*index].record;
+                let branch = record.status.as_ref().and_then(|status| status.branch.as_deref());
+                record.path.to_string_lossy().to_lowercase().contains(&filter)
+                    || branch.is_some_and(|branch| branch.to_lowercase().contains(&filter))
+            })
+            .collect();
+
+        // Entries are sorted by path already, and the sort is stable, so ties stay sorted by path
+        let entries = &self.entries;
+        let status = |index: &usize| entries[*index].record.status.as_ref();
+        match self.sort_key {
+            SortKey::Path => {}
+            SortKey::Branch => self.visible.sort_by_key(|index| status(index).and_then(|s| s.branch.clone())),
+            SortKey::Ahead => self.visible.sort_by_key(|index| status(index).and_then(|s| s.ahead)),
+            SortKey::Behind => self.visible.sort_by_key(|index| status(index).and_then(|s| s.behind)),
+            SortKey::Changes => self.visible.sort_by_key(|index| entries[*index].changes()),
+            SortKey::LastCommit => self
+                .visible
+                .sort_by_key(|index| entries[*index].last_commit.as_ref().map(|commit| commit.timestamp)),
+        }
+        if self.descending {
+            self.visible.reverse();
+        }
+
+        let selected = self.table.selected().unwrap_or(0);
+        self.table.select(Some(selected.min(self.visible.len().saturating_sub(1))));
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        let selected = self.table.selected().unwrap_or(0);
+        let last = self.visible.len().saturating_sub(1);
+        self.table.select(Some(selected.saturating_add_signed(offset).min(last)));
+    }
+
+    /// The index into `entries` of the selected row, if any row is shown.
+    fn selected(&self) -> Option<usize> {
+        self.table.selected().and_then(|row| self.visible.get(row).copied())
+    }
+
+    fn pull(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let Some(index) = self.selected() else {
+            return Ok(());
+        };
+        let record = &self.entries[index].record;
+        let path = record.path.clone();
+
+        // Same rules as a plain `pull-repos`: only fast-forward repositories without local work
+        let can_pull = record.status.as_ref().is_some_and(|status| pull_repos::is_eligible(&self.pull_args, status));
+        if !can_pull {
+            // A fast-forward leaves stashes and submodules alone, and is what brings a branch up to date
+            let harmless = [Condition::Behind, Condition::Stashed, Condition::SubmodulesOutOfSync];
+            let reason = refusal(record, &harmless, self.args.allow_untracked, "nothing to pull");
+            self.message = format!("Not pulling '{}': {}", path.display(), reason);
+            return Ok(());
+        }
+
+        self.message = format!("Pulling '{}'...", path.display());
+        terminal.draw(|frame| self.draw(frame))?;
+
+        let options = PullOptions {
+            strategy: self.pull_args.strategy,
+            autostash: self.pull_args.autostash,
+            timeout: Some(Duration::from_secs(self.args.timeout)),
+        };
+        self.message = match self.backend.pull(&path, options) {
+            Ok(()) => format!("Pulled '{}'.", path.display()),
+            Err(err) => format!("Failed to pull '{}': {:#}", path.display(), err),
+        };
+        self.refresh_entry(index);
+        Ok(())
+    }
+
+    fn push(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let Some(index) = self.selected() else {
+            return Ok(());
+        };
+        let record = &self.entries[index].record;
+        let path = record.path.clone();
+
+        // Same rules as a plain `push-repos`: only commits that won't be rejected for being behind
+        let can_push = record.status.as_ref().is_some_and(|status| push_repos::is_eligible(&self.push_args, status));
+        if !can_push {
+            let harmless = [Condition::Ahead, Condition::Stashed, Condition::SubmodulesOutOfSync];
+            let reason = refusal(record, &harmless, self.args.allow_untracked, "nothing to push");
+            self.message = format!("Not pushing '{}': {}", path.display(), reason);
+            return Ok(());
+        }
+
+        self.message = format!("Pushing '{}'...", path.display());
+        terminal.draw(|frame| self.draw(frame))?;
+
+        let options = PushOptions {
+            timeout: Some(Duration::from_secs(self.args.timeout)),
+            ..PushOptions::default()
+        };
+        self.message = match self.backend.push(&path, &options) {
+            Ok(()) => format!("Pushed '{}'.", path.display()),
+            Err(err) => format!("Failed to push '{}': {:#}", path.display(), err),
+        };
+        self.refresh_entry(index);
+        Ok(())
+    }
+
+    /// Shows the local changes of the selected repository with `git diff`, in its pager. Before
+    /// the first commit, only the staged changes have something to be compared with.
+    fn diff(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let Some(index) = self.selected() else {
+            return Ok(());
+        };
+        let path = self.entries[index].record.path.clone();
+
+        let mut command = Command::new("git");
+        let base = if has_head(&path) { "HEAD" } else { "--cached" };
+        command.arg("-C").arg(&path).args(["diff", base]);
+        self.run_outside(terminal, &path, &mut command)
+    }
+
+    /// Opens the user's shell in the selected repository, and returns to the dashboard on exit.
+    fn shell(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let Some(index) = self.selected() else {
+            return Ok(());
+        };
+        let path = self.entries[index].record.path.clone();
+
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        let mut command = Command::new(shell);
+        command.current_dir(&path);
+        self.run_outside(terminal, &path, &mut command)?;
+        self.refresh_entry(index);
+        Ok(())
+    }
+
+    /// Hands the terminal over to an interactive command, and takes it back once it exits.
+    fn run_outside(&mut self, terminal: &mut DefaultTerminal, path: &Path, command: &mut Command) -> Result<()> {
+        ratatui::restore();
+        let status = command.status();
+        enable_raw_mode()?;
+        execute!(std::io::stdout(), EnterAlternateScreen)?;
+        terminal.clear()?;
+
+        self.message = match status {
+            Ok(status) if status.success() => String::new(),
+            Ok(status) => format!("Command exited with {} in '{}'", status, path.display()),
+            Err(err) => format!("Failed to run command in '{}': {}", path.display(), err),
+        };
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [title_area, table_area, footer_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+
+        let direction = if self.descending { "descending" } else { "ascending" };
+        let title = format!(
+            "{} repositories ({} shown), sorted by {} ({})",
+            self.entries.len(),
+            self.visible.len(),
+            self.sort_key.title().to_lowercase(),
+            direction
+        );
+        frame.render_widget(Paragraph::new(title).style(Style::new().add_modifier(Modifier::BOLD)), title_area);
+
+        let header = Row::new(SortKey::ALL.map(|key| {
+            let arrow = match (key == self.sort_key, self.descending) {
+                (true, false) => " ▲",
+                (true, true) => " ▼",
+                (false, _) => "",
+            };
+            Cell::from(format!("{}{}", key.title(), arrow))
+        }))
+        .style(Style::new().add_modifier(Modifier::UNDERLINED));
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64);
+        let rows = self.visible.iter().map(|index| table_row(&self.entries[*index], now));
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(24),
+                Constraint::Length(7),
+                Constraint::Length(7),
+                Constraint::Length(9),
+                Constraint::Length(16),
+            ],
+        )
+        .header(header)
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let status_line = if self.editing_filter || !self.filter.is_empty() {
+            let cursor = if self.editing_filter { "_" } else { "" };
+            format!("Filter: {}{}   {}", self.filter, cursor, self.message)
+        } else {
+            self.message.clone()
+        };
+        frame.render_widget(Paragraph::new(vec![Line::from(status_line), Line::from(HELP)]), footer_area);
+    }
+}
+
+fn table_row(entry: &Entry, now: i64) -> Row<'static> {
+    let path = entry.record.path.display().to_string();
+    let Some(status) = &entry.record.status else {
+        let error = entry.record.error.clone().unwrap_or_default();
+        return Row::new(vec![Cell::from(path), Cell::from(error)]).style(Style::new().fg(Color::Red));
+    };
+
+    let count = |value: Option<usize>| match value {
+        Some(0) => String::new(),
+        Some(n) => n.to_string(),
+        None => "-".to_string(),
+    };
+    let last_commit = entry
+        .last_commit
+        .as_ref()
+        .map(|commit| relative_time(now - commit.timestamp))
+        .unwrap_or_default();
+
+    Row::new(vec![
+        Cell::from(path),
+        Cell::from(status.branch.clone().unwrap_or_else(|| "(detached)".to_string())),
+        Cell::from(count(status.ahead)),
+        Cell::from(count(status.behind)),
+        Cell::from(count(Some(entry.changes()))),
+        Cell::from(last_commit),
+    ])
+    .style(row_style(status))
+}
+
+/// Colors a row by its most pressing condition.
+fn row_style(status: &RepoStatus) -> Style {
+    if !status.conflicted.is_empty() || status.operation.is_some() || status.is_diverged() {
+        Style::new().fg(Color::Red)
+    } else if status.has_tracked_changes() || !status.untracked.is_empty() {
+        Style::new().fg(Color::Yellow)
+    } else if status.ahead.unwrap_or(0) > 0 || status.behind.unwrap_or(0) > 0 {
+        Style::new().fg(Color::Cyan)
+    } else {
+        Style::new()
+    }
+}
+
+/// Why a repository is not pulled or pushed: the conditions standing in the way, which are all but
+/// the `harmless` ones, or `otherwise` when none is.
+fn refusal(record: &RepoRecord, harmless: &[Condition], allow_untracked: bool, otherwise: &str) -> String {
+    let Some(status) = &record.status else {
+        return format!("its status is unknown ({})", record.error.as_deref().unwrap_or("no error given"));
+    };
+
+    let blockers: Vec<&str> = status
+        .conditions()
+        .into_iter()
+        .filter(|condition| !harmless.contains(condition))
+        .filter(|condition| !(allow_untracked && *condition == Condition::Untracked))
+        .map(|condition| condition.label())
+        .collect();
+    if blockers.is_empty() {
+        otherwise.to_string()
+    } else {
+        blockers.join(", ")
+    }
+}
@@ -0,0 +1,135 @@
+use crate::cli::RemoteOperationArgs;
+use crate::commands::git_utilities::filters::RepoFilter;
+use crate::utils::logger;
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Directory names that are never descended into while looking for repositories.
+/// They routinely contain vendored checkouts with their own `.git` that we don't manage.
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", ".git"];
+
+/// The layout of a discovered Git repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepoKind {
+    /// A regular repository with a `.git` directory.
+    Standard,
+    /// A linked worktree or submodule checkout, where `.git` is a file pointing elsewhere.
+    Worktree,
+    /// A bare repository without a working tree.
+    Bare,
+}
+
+/// A Git repository found while walking the directories given to a command.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    pub path: PathBuf,
+    pub kind: RepoKind,
+}
+
+/// Walks every directory in `args.directories` and returns the repositories found that pass
+/// `filter` (except `--status`), sorted by path.
+///
+/// The walk stops descending as soon as a repository is found, so nested repositories
+/// (vendored dependencies, submodules) are not reported separately.
+pub fn discover_repositories(args: &RemoteOperationArgs, filter: &RepoFilter) -> Result<Vec<Repository>> {
+    let mut repositories = Vec::new();
+
+    for dir in &args.directories {
+        // Confirm that the path is a valid directory
+        if !dir.is_dir() {
+            logger::error(&format!("Not a directory: '{}'", dir.display()));
+            continue;
+        }
+
+        let mut walker = WalkDir::new(dir)
+            .max_depth(args.max_depth)
+            .follow_links(args.follow_symlinks)
+            .sort_by_file_name()
+            .into_iter();
+
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    logger::warning(&format!("Failed to read directory entry: {}", err));
+                    continue;
+                }
+            };
+
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+
+            let path = entry.path();
+
+            if entry.depth() > 0 && is_skipped_dir(path) {
+                logger::debug(&format!("Skipping '{}'", path.display()));
+                walker.skip_current_dir();
+                continue;
+            }
+
+            if let Some(kind) = repo_kind(path) {
+                let relative_path = path.strip_prefix(dir).unwrap_or(path);
+                if !filter.matches(path, relative_path, kind) {
+                    logger::debug(&format!("Skipping filtered out repository '{}'", path.display()));
+                    walker.skip_current_dir();
+                    continue;
+                }
+
+                logger::debug(&format!("Found repository '{}'", path.display()));
+                repositories.push(Repository {
+                    path: path.to_path_buf(),
+                    kind,
+                });
+                walker.skip_current_dir();
+                continue;
+            }
+
+            if entry.depth() == args.max_depth {
+                logger::warning(&format!("Not a git repository: '{}'", path.display()));
+            }
+        }
+    }
+
+    repositories.sort_by(|a, b| a.path.cmp(&b.path));
+    repositories.dedup_by(|a, b| a.path == b.path);
+    Ok(repositories)
+}
+
+/// Determines whether `path` is the root of a Git repository, and of which kind.
+pub fn repo_kind(path: &Path) -> Option<RepoKind> {
+    let dot_git = path.join(".git");
+
+    if dot_git.is_dir() {
+        return Some(RepoKind::Standard);
+    }
+
+    // Worktrees and submodules have a `.git` file of the form "gitdir: <path>"
+    if dot_git.is_file() {
+        let is_gitdir_link = fs::read_to_string(&dot_git)
+            .map(|content| content.starts_with("gitdir:"))
+            .unwrap_or(false);
+        if is_gitdir_link {
+            return Some(RepoKind::Worktree);
+        }
+    }
+
+    if is_bare_repo(path) {
+        return Some(RepoKind::Bare);
+    }
+
+    None
+}
+
+/// A bare repository has the Git internals (HEAD, objects, refs) at its top level.
+fn is_bare_repo(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
+
+fn is_skipped_dir(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| SKIPPED_DIRS.contains(&name))
+}
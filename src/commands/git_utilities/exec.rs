@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use crate::cli::ExecArgs;
+use crate::commands::git_utilities::report::{exit_description, ActionResult};
+use crate::commands::git_utilities::utils::run_with_action;
+use crate::utils::logger;
+use anyhow::Context;
+
+pub fn run(args: &ExecArgs) -> Result<(), anyhow::Error> {
+    run_with_action(
+        &args.remote,
+        // Commands like `git checkout main` need a clean working tree, not a pushed or tracked branch
+        |status| !args.only_clean || status.has_safe_worktree(args.remote.allow_untracked),
+        |path, _status| {
+            if args.remote.dry_run {
+                let command: Vec<String> = args.command.iter().map(|arg| shell_quote(arg)).collect();
+                Ok(ActionResult::DryRun {
+                    command: format!("cd {} && {}", shell_quote(&path.to_string_lossy()), command.join(" ")),
+                    commits: Vec::new(),
+                })
+            } else {
+                on_ok_exec(&args.command, path)
+            }
+        },
+    )
+}
+
+/// Runs the command in the repository, and logs its output as one block once it exits.
+fn on_ok_exec(command: &[String], path: PathBuf) -> anyhow::Result<ActionResult, anyhow::Error> {
+    let (program, arguments) = command.split_first().expect("clap requires a command");
+
+    // The command runs unattended, possibly next to others, so it gets no input
+    let output = Command::new(program)
+        .args(arguments)
+        .current_dir(&path)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("Failed to run '{}' in '{}'", program, path.display()))
+        .inspect_err(|err| logger::error(&format!("{:#}", err)))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    let code = output.status.code();
+
+    let message = format!("'{}' exited with {}", path.display(), exit_description(code));
+    if output.status.success() {
+        logger::info(&message);
+    } else {
+        logger::error(&message);
+    }
+    if !stdout.trim().is_empty() {
+        logger::output(&stdout);
+    }
+    if !stderr.trim().is_empty() {
+        logger::output(&stderr);
+    }
+
+    Ok(ActionResult::Exited { code, stdout, stderr })
+}
+
+/// Quotes `arg` for a POSIX shell, so the dry-run command can be copied and run as shown. Single
+/// quotes keep everything literal but themselves, which are closed, escaped and reopened.
+fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_only_what_the_shell_would_interpret() {
+        assert_eq!(shell_quote("cargo"), "cargo");
+        assert_eq!(shell_quote("--max-depth=2"), "--max-depth=2");
+        assert_eq!(shell_quote("./scripts/lint.sh"), "./scripts/lint.sh");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("my repo"), "'my repo'");
+        assert_eq!(shell_quote("s/foo/bar/; rm -rf $HOME"), "'s/foo/bar/; rm -rf $HOME'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
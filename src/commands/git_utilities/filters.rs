@@ -0,0 +1,148 @@
+use crate::cli::{RepoFilters, StatusFilter};
+use crate::commands::git_utilities::discovery::RepoKind;
+use crate::commands::git_utilities::manifest::Manifest;
+use crate::commands::git_utilities::status::RepoStatus;
+use anyhow::{Context, Result};
+use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// `RepoFilters` compiled once, so it can be checked against every discovered repository.
+///
+/// Filters are checked from the cheapest to the most expensive (path globs, groups, the
+/// checked out branch, then remote URLs, which spawn `git`), and all of them run before the
+/// status of the repository is computed. Only `--status` needs it and is checked separately.
+pub struct RepoFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    branch: Option<String>,
+    remote_url: Option<GlobMatcher>,
+    group_members: Option<HashSet<PathBuf>>,
+    status: Vec<StatusFilter>,
+}
+
+impl RepoFilter {
+    pub fn new(filters: &RepoFilters) -> Result<Self> {
+        let include = if filters.include.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(&filters.include)?)
+        };
+
+        let remote_url = match &filters.remote_url {
+            Some(pattern) => Some(
+                Glob::new(pattern)
+                    .with_context(|| format!("Invalid --remote-url pattern '{}'", pattern))?
+                    .compile_matcher(),
+            ),
+            None => None,
+        };
+
+        // Compare canonical paths, since the manifest and the command line may spell them differently
+        let group_members = if filters.groups.is_empty() {
+            None
+        } else {
+            let manifest = Manifest::load(&filters.manifest)?;
+            Some(
+                manifest
+                    .select(&filters.groups)
+                    .iter()
+                    .filter_map(|repo| repo.path.canonicalize().ok())
+                    .collect(),
+            )
+        };
+
+        Ok(Self {
+            include,
+            exclude: build_glob_set(&filters.exclude)?,
+            branch: filters.branch.clone(),
+            remote_url,
+            group_members,
+            status: filters.status.clone(),
+        })
+    }
+
+    /// Whether a discovered repository passes every filter that doesn't need its status.
+    /// `relative_path` is its path relative to the directory it was found in.
+    pub fn matches(&self, path: &Path, relative_path: &Path, kind: RepoKind) -> bool {
+        let matches_glob = |set: &GlobSet| set.is_match(relative_path) || set.is_match(path);
+
+        if self.include.as_ref().is_some_and(|set| !matches_glob(set)) || matches_glob(&self.exclude) {
+            return false;
+        }
+
+        if let Some(members) = &self.group_members {
+            if !path.canonicalize().is_ok_and(|path| members.contains(&path)) {
+                return false;
+            }
+        }
+
+        if let Some(branch) = &self.branch {
+            if current_branch(path, kind).as_deref() != Some(branch.as_str()) {
+                return false;
+            }
+        }
+
+        if let Some(matcher) = &self.remote_url {
+            if !remote_urls(path).iter().any(|url| matcher.is_match(url)) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Whether a repository is in one of the states selected with `--status`, if any.
+    pub fn matches_status(&self, status: &RepoStatus) -> bool {
+        self.status.is_empty()
+            || self.status.iter().any(|filter| match filter {
+                StatusFilter::Dirty => status.has_tracked_changes() || !status.untracked.is_empty(),
+                StatusFilter::Ahead => status.ahead.unwrap_or(0) > 0,
+                StatusFilter::Behind => status.behind.unwrap_or(0) > 0,
+            })
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).with_context(|| format!("Invalid glob pattern '{}'", pattern))?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Reads the checked out branch straight from `HEAD`, without spawning `git`.
+/// Returns `None` on a detached HEAD.
+fn current_branch(path: &Path, kind: RepoKind) -> Option<String> {
+    let git_dir = match kind {
+        RepoKind::Standard => path.join(".git"),
+        RepoKind::Bare => path.to_path_buf(),
+        // The `.git` file points to the worktree's own git directory, which has its own HEAD
+        RepoKind::Worktree => {
+            let content = fs::read_to_string(path.join(".git")).ok()?;
+            path.join(content.strip_prefix("gitdir:")?.trim())
+        }
+    };
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    head.trim().strip_prefix("ref: refs/heads/").map(str::to_string)
+}
+
+/// The URLs of every remote of the repository.
+fn remote_urls(path: &Path) -> Vec<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(["config", "--get-regexp", r"^remote\..*\.url$"])
+        .output();
+
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once(' ').map(|(_, url)| url.to_string()))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
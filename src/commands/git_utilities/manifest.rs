@@ -0,0 +1,175 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A workspace manifest: the repositories a team works on and where they live locally.
+///
+/// ```toml
+/// [[repos]]
+/// url = "git@github.com:acme/api.git"
+/// path = "services/api"
+/// branch = "main"
+/// groups = ["backend"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// The directory the repository paths are relative to. Once loaded, it is resolved against
+    /// the manifest's directory, which is also the default.
+    #[serde(default)]
+    pub root: PathBuf,
+    #[serde(default, rename = "repos")]
+    pub repositories: Vec<ManifestRepo>,
+}
+
+/// A repository listed in the manifest.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestRepo {
+    /// Where to clone the repository from.
+    pub url: String,
+    /// Where the repository lives. Once loaded, it is resolved against the manifest's root.
+    /// Defaults to the last component of the URL, without `.git`.
+    #[serde(default)]
+    pub path: PathBuf,
+    /// The branch to check out when cloning. Defaults to the remote's default branch.
+    pub branch: Option<String>,
+    /// Names used to select a subset of the repositories, e.g. `backend`.
+    #[serde(default, alias = "tags")]
+    pub groups: Vec<String>,
+}
+
+impl Manifest {
+    /// Reads a TOML or YAML manifest, depending on the file extension, and resolves every path.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest '{}'", path.display()))?;
+
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        let mut manifest: Manifest = match extension {
+            "toml" => toml::from_str(&content)
+                .with_context(|| format!("Invalid manifest '{}'", path.display()))?,
+            "yaml" | "yml" => serde_yaml::from_str(&content)
+                .with_context(|| format!("Invalid manifest '{}'", path.display()))?,
+            _ => bail!(
+                "Unsupported manifest format '{}', expected a .toml, .yaml or .yml file",
+                path.display()
+            ),
+        };
+
+        // Relative paths are relative to the manifest, not to the current directory
+        let manifest_dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        manifest.root = manifest_dir.join(&manifest.root);
+
+        let mut seen = HashSet::new();
+        for repo in &mut manifest.repositories {
+            if repo.path.as_os_str().is_empty() {
+                repo.path = PathBuf::from(name_from_url(&repo.url));
+            }
+            repo.path = manifest.root.join(&repo.path);
+
+            if !seen.insert(repo.path.clone()) {
+                bail!("The manifest lists '{}' more than once", repo.path.display());
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// The repositories in any of `groups`, or every repository if `groups` is empty.
+    pub fn select(&self, groups: &[String]) -> Vec<&ManifestRepo> {
+        self.repositories
+            .iter()
+            .filter(|repo| groups.is_empty() || repo.groups.iter().any(|group| groups.contains(group)))
+            .collect()
+    }
+}
+
+/// The repository name in a clone URL, e.g. `api` for `git@github.com:acme/api.git`.
+fn name_from_url(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    let name = url.rsplit(['/', ':']).next().unwrap_or(url);
+    name.strip_suffix(".git").unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_repositories_after_their_url() {
+        let cases = [
+            ("git@github.com:acme/api.git", "api"),
+            ("https://github.com/acme/web", "web"),
+            ("https://github.com/acme/web/", "web"),
+            ("ssh://git@host:2222/acme/tools.git", "tools"),
+            ("host:lonely.git", "lonely"),
+            ("../relative/path.git", "path"),
+            ("plain", "plain"),
+        ];
+        for (url, name) in cases {
+            assert_eq!(name_from_url(url), name, "for {}", url);
+        }
+    }
+
+    #[test]
+    fn selects_repositories_by_group() {
+        let repo = |url: &str, groups: &[&str]| ManifestRepo {
+            url: url.to_string(),
+            path: PathBuf::from(name_from_url(url)),
+            branch: None,
+            groups: groups.iter().map(|group| group.to_string()).collect(),
+        };
+        let manifest = Manifest {
+            root: PathBuf::from("."),
+            repositories: vec![
+                repo("api.git", &["backend"]),
+                repo("web.git", &["frontend"]),
+                repo("docs.git", &[]),
+            ],
+        };
+        let urls = |groups: &[&str]| {
+            let groups: Vec<String> = groups.iter().map(|group| group.to_string()).collect();
+            manifest
+                .select(&groups)
+                .into_iter()
+                .map(|repo| repo.url.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(urls(&[]), ["api.git", "web.git", "docs.git"]);
+        assert_eq!(urls(&["backend"]), ["api.git"]);
+        assert_eq!(urls(&["backend", "frontend"]), ["api.git", "web.git"]);
+        assert!(urls(&["mobile"]).is_empty());
+    }
+
+    #[test]
+    fn resolves_paths_against_the_manifest() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("fafnir.toml");
+        fs::write(
+            &path,
+            "root = \"work\"\n\n[[repos]]\nurl = \"git@host:acme/api.git\"\n\n[[repos]]\nurl = \"x\"\npath = \"a/b\"\n",
+        )
+        .unwrap();
+
+        let manifest = Manifest::load(&path).unwrap();
+        let paths: Vec<_> = manifest.repositories.iter().map(|repo| repo.path.clone()).collect();
+        assert_eq!(paths, [dir.path().join("work/api"), dir.path().join("work/a/b")]);
+    }
+
+    #[test]
+    fn rejects_duplicate_paths() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("fafnir.toml");
+        fs::write(&path, "[[repos]]\nurl = \"a/api.git\"\n\n[[repos]]\nurl = \"b/api\"\n").unwrap();
+
+        let err = Manifest::load(&path).unwrap_err();
+        assert!(err.to_string().contains("more than once"), "{}", err);
+    }
+}
@@ -1,5 +1,23 @@
+pub mod backend;
+pub mod branch;
 pub mod check_repos;
 pub mod collect;
+pub mod commit_repos;
+#[cfg(feature = "tui")]
+pub mod dashboard;
+pub mod discovery;
+pub mod exec;
+pub mod filters;
+pub mod manifest;
+pub mod process;
 pub mod pull_repos;
 pub mod push_repos;
+pub mod report;
+pub mod repo_stats;
+pub mod stash_repos;
+pub mod status;
+pub mod sync;
+#[cfg(test)]
+pub mod test_support;
 pub mod utils;
+pub mod watch;
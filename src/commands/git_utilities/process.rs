@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::io::Read;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use wait_timeout::ChildExt;
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Makes git fail instead of prompting on the terminal, e.g. for a password, an SSH key
+/// passphrase or an unknown host key.
+pub fn init_non_interactive(enabled: bool) {
+    NON_INTERACTIVE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_non_interactive() -> bool {
+    NON_INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// A process that `output_with_timeout` killed because it was still running after the timeout.
+#[derive(Debug)]
+pub struct TimedOut {
+    pub timeout: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out after {}s", self.timeout.as_secs())
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Runs `command` to completion like `Command::output`, but kills it if it is still running
+/// after `timeout`.
+///
+/// In non-interactive mode the command runs in its own process group, which is killed as a
+/// whole, so the ssh that git started dies with it instead of holding the output pipes open.
+/// Otherwise it stays in ours, as only the foreground process group can prompt on the terminal.
+pub fn output_with_timeout(command: &mut Command, timeout: Duration) -> Result<Output> {
+    run_with_timeout(command, timeout, cfg!(unix) && is_non_interactive())
+}
+
+fn run_with_timeout(command: &mut Command, timeout: Duration, own_group: bool) -> Result<Output> {
+    #[cfg(unix)]
+    if own_group {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to spawn process")?;
+
+    // Drain the pipes on separate threads so a chatty process can't block on a full pipe
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+
+    let status = match child.wait_timeout(timeout)? {
+        Some(status) => status,
+        None => {
+            kill(&mut child, own_group);
+            let _ = child.wait();
+            // With the whole group dead nothing holds the pipes anymore, so the readers are done
+            // soon. Otherwise they are left to finish whenever the processes git started do.
+            if own_group {
+                for reader in [stdout, stderr].into_iter().flatten() {
+                    join_reader(reader);
+                }
+            }
+            return Err(TimedOut { timeout }.into());
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.map(join_reader).unwrap_or_default(),
+        stderr: stderr.map(join_reader).unwrap_or_default(),
+    })
+}
+
+/// Kills `child`, and every process in its group if it leads one.
+fn kill(child: &mut Child, group: bool) {
+    if group {
+        #[cfg(unix)]
+        {
+            // SAFETY: `kill` has no memory safety requirements. The child was not waited for yet,
+            // so its id, which is also the id of its group, can't have been reused.
+            unsafe {
+                libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+            }
+            return;
+        }
+    }
+    let _ = child.kill();
+}
+
+fn read_to_end<R: Read + Send + 'static>(mut reader: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer);
+        buffer
+    })
+}
+
+fn join_reader(handle: thread::JoinHandle<Vec<u8>>) -> Vec<u8> {
+    handle.join().unwrap_or_default()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn kills_the_processes_the_command_started() {
+        // The background `sleep` keeps stdout open, as ssh does for git
+        let mut command = Command::new("sh");
+        command.args(["-c", "sleep 30 & echo started; wait"]);
+
+        let started = Instant::now();
+        let err = run_with_timeout(&mut command, Duration::from_millis(500), true).unwrap_err();
+        assert!(err.is::<TimedOut>(), "{:#}", err);
+        assert!(started.elapsed() < Duration::from_secs(10), "waited for {:?}", started.elapsed());
+    }
+
+    #[test]
+    fn returns_the_output_of_a_command_that_finishes() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2; exit 3"]);
+
+        let output = run_with_timeout(&mut command, Duration::from_secs(10), true).unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+}
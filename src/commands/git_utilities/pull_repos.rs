@@ -1,21 +1,155 @@
-use std::path::PathBuf;
-use std::process::Command;
-use crate::cli::RemoteOperationArgs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::cli::{PullReposArgs, PullStrategy};
+use crate::commands::git_utilities::backend::error::{LeftConflicted, NeedsIntervention};
+use crate::commands::git_utilities::backend::cli::{pull_args, SUBMODULE_UPDATE_ARGS};
+use crate::commands::git_utilities::backend::{self, GitBackend, PullOptions};
+use crate::commands::git_utilities::report::ActionResult;
+use crate::commands::git_utilities::status::RepoStatus;
 use crate::commands::git_utilities::utils::run_with_action;
 use crate::utils::logger;
 
-pub fn run(args: &RemoteOperationArgs) -> Result<(), anyhow::Error> {
-    run_with_action(args, on_ok_pull)
+pub fn run(args: &PullReposArgs) -> Result<(), anyhow::Error> {
+    let backend = backend::create(args.remote.backend);
+    let options = PullOptions {
+        strategy: args.strategy,
+        autostash: args.autostash,
+        timeout: Some(Duration::from_secs(args.remote.timeout)),
+    };
+
+    run_with_action(
+        &args.remote,
+        |status| is_eligible(args, status),
+        |path, _status| {
+            if args.remote.dry_run {
+                plan_pull(backend.as_ref(), args, options, path)
+            } else {
+                on_ok_pull(backend.as_ref(), args, options, path)
+            }
+        },
+    )
 }
 
-fn on_ok_pull(path: PathBuf) -> anyhow::Result<(), anyhow::Error> {
+/// Whether `git pull` can run with the chosen strategy without touching local work.
+pub(crate) fn is_eligible(args: &PullReposArgs, status: &RepoStatus) -> bool {
+    if status.is_detached() || status.upstream.is_none() || status.operation.is_some() {
+        return false;
+    }
+
+    // With --autostash local changes are set aside during the pull, conflicts are not
+    let worktree_ok = if args.autostash {
+        status.conflicted.is_empty()
+            && (args.remote.allow_untracked || status.untracked.is_empty())
+    } else {
+        status.has_safe_worktree(args.remote.allow_untracked)
+    };
+
+    // Only rebase and merge can integrate upstream changes with local commits
+    let history_ok = match args.strategy {
+        PullStrategy::FfOnly => status.ahead == Some(0),
+        PullStrategy::Rebase | PullStrategy::Merge => status.ahead.is_some(),
+    };
+
+    // After a fetch we know whether there is anything to pull at all
+    let has_changes = !args.remote.fetch || status.behind.unwrap_or(0) > 0;
+
+    worktree_ok && history_ok && has_changes
+}
+
+fn on_ok_pull(
+    backend: &dyn GitBackend,
+    args: &PullReposArgs,
+    options: PullOptions,
+    path: PathBuf,
+) -> anyhow::Result<ActionResult, anyhow::Error> {
     logger::debug(&format!("Pulling in '{}'... ", path.display()));
 
-    let output = Command::new("git").arg("pull").current_dir(path.clone()).output()?;
+    backend.pull(&path, options).inspect_err(|err| {
+        if err.is::<NeedsIntervention>() {
+            logger::warning(&format!("Pull in '{}' needs manual intervention: {}", path.display(), err));
+        } else if err.is::<LeftConflicted>() {
+            logger::warning(&format!("Pull in '{}' left conflicts to resolve: {}", path.display(), err));
+        } else {
+            logger::error(&format!("Failed to pull from the origin in '{}': {:#}", path.display(), err));
+        }
+    })?;
 
-    if !output.status.success() {
-        logger::error(&format!("Failed to pull from the origin in '{}'", path.display()));
+    // The pull itself succeeded, so a failed update is reported apart from failed pulls
+    if args.update_submodules && has_submodules(&path) {
+        logger::debug(&format!("Updating the submodules in '{}'... ", path.display()));
+        if let Err(err) = backend.update_submodules(&path, Duration::from_secs(args.remote.timeout)) {
+            logger::error(&format!("Pulled, but failed to update the submodules in '{}': {:#}", path.display(), err));
+            return Ok(ActionResult::SubmodulesNotUpdated {
+                reason: format!("{:#}", err),
+            });
+        }
     }
 
-    Ok(())
+    Ok(ActionResult::Succeeded)
+}
+
+/// Describes the pull `on_ok_pull` would do, without running it.
+fn plan_pull(
+    backend: &dyn GitBackend,
+    args: &PullReposArgs,
+    options: PullOptions,
+    path: PathBuf,
+) -> anyhow::Result<ActionResult, anyhow::Error> {
+    let mut command = format!("git -C {} {}", path.display(), pull_args(options).join(" "));
+    if args.update_submodules && has_submodules(&path) {
+        command.push_str(&format!(" && git -C {} {}", path.display(), SUBMODULE_UPDATE_ARGS.join(" ")));
+    }
+
+    Ok(ActionResult::DryRun {
+        command,
+        commits: backend.commits(&path, "@{u}", &["HEAD"])?,
+    })
+}
+
+fn has_submodules(path: &Path) -> bool {
+    path.join(".gitmodules").is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::RemoteOperationArgs;
+    use crate::commands::git_utilities::backend::cli::CliBackend;
+    use crate::commands::git_utilities::test_support::{git, Fixture};
+
+    #[test]
+    fn failed_submodule_update_keeps_the_pull() {
+        let fixture = Fixture::new();
+        let submodule = fixture.repo("submodule");
+        let (remote, local) = fixture.repo_with_remote("origin.git", "local");
+
+        // Someone else adds a submodule, which then disappears, so it can't be cloned anymore
+        let other = fixture.path().join("other");
+        git(fixture.path(), &["clone", "-q", remote.to_str().unwrap(), other.to_str().unwrap()]);
+        git(&other, &["config", "user.name", "Other"]);
+        git(&other, &["config", "user.email", "other@example.com"]);
+        git(
+            &other,
+            &["-c", "protocol.file.allow=always", "submodule", "add", "-q", submodule.to_str().unwrap(), "vendor/sub"],
+        );
+        git(&other, &["commit", "-q", "-m", "Add a submodule"]);
+        git(&other, &["push", "-q"]);
+        std::fs::remove_dir_all(&submodule).unwrap();
+
+        let args = PullReposArgs {
+            remote: RemoteOperationArgs::default(),
+            strategy: PullStrategy::FfOnly,
+            autostash: false,
+            update_submodules: true,
+        };
+        let options = PullOptions {
+            strategy: args.strategy,
+            autostash: false,
+            timeout: None,
+        };
+        let result = on_ok_pull(&CliBackend, &args, options, local.clone()).unwrap();
+
+        assert!(matches!(result, ActionResult::SubmodulesNotUpdated { .. }), "{:?}", result);
+        assert_eq!(git(&local, &["rev-parse", "HEAD"]), git(&other, &["rev-parse", "HEAD"]));
+    }
 }
@@ -1,21 +1,89 @@
 use std::path::PathBuf;
-use std::process::Command;
-use crate::cli::RemoteOperationArgs;
-use crate::commands::git_utilities::utils::run_with_action;
+use std::time::Duration;
+use crate::cli::PushReposArgs;
+use crate::commands::git_utilities::backend::cli::push_args;
+use crate::commands::git_utilities::backend::{self, GitBackend, PushOptions};
+use crate::commands::git_utilities::report::ActionResult;
+use crate::commands::git_utilities::status::RepoStatus;
+use crate::commands::git_utilities::utils::{run_with_action, run_with_confirmed_action};
 use crate::utils::logger;
 
-pub fn run(args: &RemoteOperationArgs) -> Result<(), anyhow::Error> {
-    run_with_action(args, on_ok_push)
+pub fn run(args: &PushReposArgs) -> Result<(), anyhow::Error> {
+    let backend = backend::create(args.remote.backend);
+    let options = PushOptions {
+        remote: args.remote_name.clone(),
+        set_upstream: args.set_upstream,
+        tags: args.tags,
+        force_with_lease: args.force_with_lease,
+        timeout: Some(Duration::from_secs(args.remote.timeout)),
+    };
+
+    let is_eligible = |status: &RepoStatus| is_eligible(args, status);
+    let on_ok = |path: PathBuf, status: &RepoStatus| {
+        if args.remote.dry_run {
+            plan_push(backend.as_ref(), &options, path, status)
+        } else {
+            on_ok_push(backend.as_ref(), &options, path)
+        }
+    };
+
+    // Force-pushing can throw away commits on the remote, so make the user confirm it
+    if args.force_with_lease {
+        run_with_confirmed_action(
+            &args.remote,
+            "Force-push (with lease) to these repositories?",
+            args.yes,
+            is_eligible,
+            on_ok,
+        )
+    } else {
+        run_with_action(&args.remote, is_eligible, on_ok)
+    }
 }
 
-fn on_ok_push(path: PathBuf) -> anyhow::Result<(), anyhow::Error> {
+/// Whether `git push` has something to push and won't be rejected with the chosen options.
+pub(crate) fn is_eligible(args: &PushReposArgs, status: &RepoStatus) -> bool {
+    if !status.has_safe_worktree(args.remote.allow_untracked) || status.is_detached() {
+        return false;
+    }
+
+    match (status.ahead, status.behind) {
+        // Without upstream there is nothing to compare against, so push only to create one
+        (None, _) | (_, None) => args.set_upstream,
+        // Local commits on a diverged branch would be rejected, unless they overwrite the remote
+        (Some(ahead), Some(behind)) => {
+            let has_commits = ahead > 0 || args.tags;
+            has_commits && (behind == 0 || args.force_with_lease)
+        }
+    }
+}
+
+fn on_ok_push(
+    backend: &dyn GitBackend,
+    options: &PushOptions,
+    path: PathBuf,
+) -> anyhow::Result<ActionResult, anyhow::Error> {
     logger::debug(&format!("Pushing to '{}'... ", path.display()));
 
-    let output = Command::new("git").arg("push").current_dir(path.clone()).output()?;
+    backend.push(&path, options).inspect_err(|err| {
+        logger::error(&format!("Failed to push in '{}': {:#}", path.display(), err));
+    })?;
 
-    if !output.status.success() {
-        logger::error(&format!("Failed to push to origin in '{}'", path.display()));
-    }
+    Ok(ActionResult::Succeeded)
+}
+
+/// Describes the push `on_ok_push` would do, without running it.
+fn plan_push(
+    backend: &dyn GitBackend,
+    options: &PushOptions,
+    path: PathBuf,
+    status: &RepoStatus,
+) -> anyhow::Result<ActionResult, anyhow::Error> {
+    // Without upstream, every commit not on any remote branch would be published
+    let exclude = if status.ahead.is_some() { "@{u}" } else { "refs/remotes/*" };
 
-    Ok(())
+    Ok(ActionResult::DryRun {
+        command: format!("git -C {} {}", path.display(), push_args(&path, options)?.join(" ")),
+        commits: backend.commits(&path, "HEAD", &[exclude])?,
+    })
 }
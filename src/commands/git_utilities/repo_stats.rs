@@ -0,0 +1,233 @@
+use crate::cli::{RemoteOperationArgs, RepoStatsArgs, ReportFormat};
+use crate::commands::git_utilities::backend::{self, GitBackend};
+use crate::commands::git_utilities::filters::RepoFilter;
+use crate::commands::git_utilities::report::RepoRecord;
+use crate::commands::git_utilities::utils::{collect_statuses, human_size, parallel_map, print_table, relative_time};
+use crate::utils::logger;
+use anyhow::{bail, Context, Result};
+use serde::{Serialize, Serializer};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+/// The activity of a repository, as reported by `repo-stats`. Flat, so it maps to CSV columns.
+#[derive(Debug, Default, Serialize)]
+struct RepoStats {
+    path: PathBuf,
+    branch: Option<String>,
+    #[serde(serialize_with = "rfc3339")]
+    last_commit_date: Option<i64>,
+    last_commit_author: Option<String>,
+    /// Commits on the checked-out branch within the `--since` window.
+    recent_commits: Option<usize>,
+    local_branches: Option<usize>,
+    remote_branches: Option<usize>,
+    oldest_unmerged_branch: Option<String>,
+    #[serde(serialize_with = "rfc3339")]
+    oldest_unmerged_date: Option<i64>,
+    /// Everything inside the repository directory, work tree and `.git` included.
+    size_bytes: u64,
+    /// Why some of the statistics are missing, if they are.
+    error: Option<String>,
+}
+
+/// The formats `--export` can write, picked by the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+pub fn run(args: &RepoStatsArgs) -> Result<()> {
+    let remote = RemoteOperationArgs {
+        directories: args.directories.clone(),
+        max_depth: args.max_depth,
+        follow_symlinks: args.follow_symlinks,
+        filters: args.filters.clone(),
+        jobs: args.jobs,
+        backend: args.backend,
+        format: args.format,
+        ..RemoteOperationArgs::default()
+    };
+
+    // Reject an unknown export format before spending time on the repositories
+    let export = args
+        .export
+        .as_ref()
+        .map(|path| Ok::<_, anyhow::Error>((path, export_format(path)?)))
+        .transpose()?;
+
+    // Keep stdout clean for machine-readable reports
+    if remote.format != ReportFormat::Text {
+        logger::init_stderr(true);
+    }
+
+    // 1. Find and check every repository inside the directories passed as arguments
+    let backend = backend::create(remote.backend);
+    let filter = RepoFilter::new(&remote.filters)?;
+    let records = collect_statuses(&remote, &filter, backend.as_ref())?;
+
+    // 2. Gather the statistics of each of them
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO).as_secs() as i64;
+    let since = now - args.since.as_secs() as i64;
+    let stats = parallel_map(remote.jobs, &records, |record| {
+        repo_stats(backend.as_ref(), record, since, &args.remote_name)
+    });
+
+    // 3. Print them, and write them to the export file if asked to
+    print(&stats, remote.format, args.since, now)?;
+    if let Some((path, format)) = export {
+        write_export(&stats, path, format)?;
+        logger::info(&format!("Statistics written to '{}'", path.display()));
+    }
+
+    Ok(())
+}
+
+fn export_format(path: &Path) -> Result<ExportFormat> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => Ok(ExportFormat::Csv),
+        Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(ExportFormat::Json),
+        _ => bail!("Cannot tell the export format of '{}', use a .csv or .json file", path.display()),
+    }
+}
+
+fn repo_stats(backend: &dyn GitBackend, record: &RepoRecord, since: i64, remote: &str) -> RepoStats {
+    let path = &record.path;
+    let mut stats = RepoStats {
+        path: path.clone(),
+        size_bytes: size_on_disk(path),
+        ..RepoStats::default()
+    };
+
+    let Some(status) = &record.status else {
+        stats.error = record.error.clone();
+        return stats;
+    };
+    stats.branch = status.branch.clone();
+
+    let result = backend.last_commit(path).and_then(|last_commit| {
+        if let Some(commit) = last_commit {
+            stats.last_commit_date = Some(commit.timestamp);
+            stats.last_commit_author = Some(commit.author);
+        }
+
+        let activity = backend.activity(path, since, remote)?;
+        stats.recent_commits = Some(activity.recent_commits);
+        stats.local_branches = Some(activity.local_branches);
+        stats.remote_branches = Some(activity.remote_branches);
+        if let Some((name, timestamp)) = activity.oldest_unmerged {
+            stats.oldest_unmerged_branch = Some(name);
+            stats.oldest_unmerged_date = Some(timestamp);
+        }
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        logger::error(&format!("Failed to gather statistics for '{}': {:#}", path.display(), err));
+        stats.error = Some(format!("{:#}", err));
+    }
+    stats
+}
+
+/// The total size of the files inside `path`, without following symbolic links. Files that
+/// can't be read are left out.
+fn size_on_disk(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn print(stats: &[RepoStats], format: ReportFormat, window: Duration, now: i64) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    match format {
+        ReportFormat::Text => print_text(stats, window, now),
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, stats).context("Failed to write JSON report")?;
+            writeln!(stdout)?;
+        }
+        ReportFormat::Ndjson => {
+            for repo in stats {
+                serde_json::to_writer(&mut stdout, repo).context("Failed to write NDJSON report")?;
+                writeln!(stdout)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_text(stats: &[RepoStats], window: Duration, now: i64) {
+    let header = [
+        "REPOSITORY".to_string(),
+        "LAST COMMIT".to_string(),
+        "AUTHOR".to_string(),
+        format!("COMMITS ({})", humantime::format_duration(window)),
+        "BRANCHES".to_string(),
+        "OLDEST UNMERGED".to_string(),
+        "SIZE".to_string(),
+    ];
+    let rows: Vec<[String; 7]> = stats.iter().map(|repo| table_row(repo, now)).collect();
+    print_table(header, &rows);
+}
+
+fn table_row(repo: &RepoStats, now: i64) -> [String; 7] {
+    let path = repo.path.display().to_string();
+    let size = human_size(repo.size_bytes);
+    if let Some(error) = &repo.error {
+        let dash = || "-".to_string();
+        return [path, format!("({})", error), dash(), dash(), dash(), dash(), size];
+    }
+
+    let count = |value: Option<usize>| value.map_or_else(|| "-".to_string(), |n| n.to_string());
+    let last_commit = repo
+        .last_commit_date
+        .map_or_else(|| "never".to_string(), |timestamp| relative_time(now - timestamp));
+    let branches = format!("{} local, {} remote", count(repo.local_branches), count(repo.remote_branches));
+    let oldest_unmerged = match (&repo.oldest_unmerged_branch, repo.oldest_unmerged_date) {
+        (Some(name), Some(timestamp)) => format!("{} ({})", name, relative_time(now - timestamp)),
+        _ => "-".to_string(),
+    };
+
+    [
+        path,
+        last_commit,
+        repo.last_commit_author.clone().unwrap_or_else(|| "-".to_string()),
+        count(repo.recent_commits),
+        branches,
+        oldest_unmerged,
+        size,
+    ]
+}
+
+fn write_export(stats: &[RepoStats], path: &Path, format: ExportFormat) -> Result<()> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_path(path)
+                .with_context(|| format!("Failed to create '{}'", path.display()))?;
+            for repo in stats {
+                writer.serialize(repo).context("Failed to write CSV export")?;
+            }
+            writer.flush()?;
+        }
+        ExportFormat::Json => {
+            let file = std::fs::File::create(path).with_context(|| format!("Failed to create '{}'", path.display()))?;
+            let mut writer = std::io::BufWriter::new(file);
+            serde_json::to_writer_pretty(&mut writer, stats).context("Failed to write JSON export")?;
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Serializes a Unix timestamp as an RFC 3339 date, e.g. `2024-05-01T12:00:00Z`.
+fn rfc3339<S: Serializer>(timestamp: &Option<i64>, serializer: S) -> Result<S::Ok, S::Error> {
+    let date = timestamp
+        .and_then(|seconds| u64::try_from(seconds).ok())
+        .map(|seconds| humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(seconds)).to_string());
+    date.serialize(serializer)
+}
@@ -0,0 +1,355 @@
+use crate::cli::ReportFormat;
+use crate::commands::git_utilities::backend::error::FailureKind;
+use crate::commands::git_utilities::status::{Condition, RepoStatus};
+use crate::utils::logger;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Everything known about a single repository once `run_with_action` is done with it.
+#[derive(Debug, Serialize)]
+pub struct RepoRecord {
+    pub path: PathBuf,
+    /// Every condition that makes the repository not clean. Empty for clean repositories.
+    pub conditions: Vec<Condition>,
+    /// `None` when the status of the repository could not be determined.
+    pub status: Option<RepoStatus>,
+    /// Why `--fetch` failed for this repository, if it did.
+    pub fetch_error: Option<String>,
+    /// The result of the command's action, or `None` if it did not run on this repository.
+    pub action: Option<ActionResult>,
+    /// Why the repository could not be checked, if it couldn't.
+    pub error: Option<String>,
+}
+
+/// The result of running a command's action (e.g. `git pull`) on a repository.
+#[derive(Debug, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum ActionResult {
+    Succeeded,
+    Failed {
+        kind: FailureKind,
+        error: String,
+        /// The last lines of git's error output, if the failure came from git.
+        stderr: String,
+    },
+    /// The action stopped half-way, was rolled back, and has to be completed by hand.
+    NeedsIntervention { reason: String },
+    /// The action finished, but left conflicts in the working tree to resolve by hand.
+    LeftConflicted { reason: String },
+    /// The pull succeeded, but updating the submodules afterwards didn't. The pulled commits stay.
+    SubmodulesNotUpdated { reason: String },
+    /// `--dry-run` was given: the command that would have run, and the commits it would move.
+    DryRun { command: String, commits: Vec<String> },
+    /// `exec` ran its command. A non-zero `code`, or none when killed by a signal, is a failure.
+    Exited {
+        code: Option<i32>,
+        stdout: String,
+        stderr: String,
+    },
+}
+
+/// Repository counts. `conditions` always lists every condition, even when its count is zero.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub total: usize,
+    pub clean: usize,
+    pub failed: usize,
+    pub needs_intervention: usize,
+    pub left_conflicted: usize,
+    pub submodules_not_updated: usize,
+    /// Repositories whose remote needed credentials that git was not allowed to prompt for.
+    pub authentication_required: usize,
+    pub conditions: BTreeMap<Condition, usize>,
+}
+
+/// The final report of a batch Git command.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub repositories: Vec<RepoRecord>,
+    pub summary: Summary,
+}
+
+/// A line of NDJSON output, tagged with its kind.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum NdjsonLine<'a> {
+    Repository(&'a RepoRecord),
+    Summary(&'a Summary),
+}
+
+impl Report {
+    /// Builds a report from the records, which must already be sorted.
+    pub fn new(repositories: Vec<RepoRecord>) -> Self {
+        let mut summary = Summary {
+            total: repositories.len(),
+            conditions: Condition::ALL.iter().map(|c| (*c, 0)).collect(),
+            ..Summary::default()
+        };
+
+        for record in &repositories {
+            if record.status.is_some() && record.conditions.is_empty() {
+                summary.clean += 1;
+            }
+            for condition in &record.conditions {
+                *summary.conditions.entry(*condition).or_default() += 1;
+            }
+            match record.action {
+                Some(ActionResult::Failed { kind: FailureKind::AuthenticationRequired, .. }) => {
+                    summary.authentication_required += 1
+                }
+                Some(ActionResult::Failed { .. }) => summary.failed += 1,
+                Some(ActionResult::Exited { code, .. }) if code != Some(0) => summary.failed += 1,
+                Some(ActionResult::NeedsIntervention { .. }) => summary.needs_intervention += 1,
+                Some(ActionResult::LeftConflicted { .. }) => summary.left_conflicted += 1,
+                Some(ActionResult::SubmodulesNotUpdated { .. }) => summary.submodules_not_updated += 1,
+                _ if record.error.is_some() => summary.failed += 1,
+                _ => {}
+            }
+        }
+
+        Self {
+            repositories,
+            summary,
+        }
+    }
+
+    /// Prints the report to stdout in the requested format.
+    pub fn print(&self, format: ReportFormat) -> Result<()> {
+        match format {
+            ReportFormat::Text => self.print_text(),
+            ReportFormat::Json => {
+                let mut stdout = std::io::stdout().lock();
+                serde_json::to_writer_pretty(&mut stdout, self)
+                    .context("Failed to write JSON report")?;
+                writeln!(stdout)?;
+                Ok(())
+            }
+            ReportFormat::Ndjson => {
+                let mut stdout = std::io::stdout().lock();
+                for record in &self.repositories {
+                    serde_json::to_writer(&mut stdout, &NdjsonLine::Repository(record))
+                        .context("Failed to write NDJSON report")?;
+                    writeln!(stdout)?;
+                }
+                serde_json::to_writer(&mut stdout, &NdjsonLine::Summary(&self.summary))
+                    .context("Failed to write NDJSON report")?;
+                writeln!(stdout)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Prints the human-readable summary to the console, one section per condition.
+    fn print_text(&self) -> Result<()> {
+        for condition in Condition::ALL {
+            let records = self.with_condition(condition);
+            if records.is_empty() {
+                continue;
+            }
+
+            logger::info(&format!(
+                "The following directories {}:",
+                condition.description()
+            ));
+            for (record, status) in records {
+                match status.detail(condition) {
+                    Some(detail) => println!("{} ({})", record.path.display(), detail),
+                    None => println!("{}", record.path.display()),
+                }
+
+                if condition == Condition::SubmodulesOutOfSync {
+                    for submodule in &status.submodules {
+                        println!("    {} ({})", submodule.path, submodule.problems());
+                    }
+                }
+
+                if condition == Condition::NoUpstream {
+                    let branch = status.branch.as_deref().unwrap_or("HEAD");
+                    match status.matching_remote_branches.first() {
+                        Some(remote_branch) => logger::info(&format!(
+                            "Remote branch '{}' exists. To link it, run: git -C {} branch --set-upstream-to={} {}",
+                            remote_branch,
+                            record.path.display(),
+                            remote_branch,
+                            branch
+                        )),
+                        None => logger::info(&format!(
+                            "No remote branch named '{}' exists. To publish it, run push-repos with --set-upstream",
+                            branch
+                        )),
+                    }
+                }
+            }
+        }
+
+        let fetch_failed: Vec<&RepoRecord> = self
+            .repositories
+            .iter()
+            .filter(|record| record.fetch_error.is_some())
+            .collect();
+        if !fetch_failed.is_empty() {
+            logger::warning("The following directories could not be fetched, so their status may be outdated:");
+            for record in fetch_failed {
+                println!(
+                    "{} ({})",
+                    record.path.display(),
+                    record.fetch_error.as_deref().unwrap_or_default()
+                );
+            }
+        }
+
+        let planned: Vec<(&RepoRecord, &str, &[String])> = self
+            .repositories
+            .iter()
+            .filter_map(|record| match &record.action {
+                Some(ActionResult::DryRun { command, commits }) => {
+                    Some((record, command.as_str(), commits.as_slice()))
+                }
+                _ => None,
+            })
+            .collect();
+        if !planned.is_empty() {
+            logger::info("Dry run: the following commands would run:");
+            for (record, command, commits) in planned {
+                println!("{}", record.path.display());
+                println!("    $ {}", command);
+                for commit in commits {
+                    println!("    {}", commit);
+                }
+            }
+        }
+
+        let succeeded: Vec<&RepoRecord> = self
+            .repositories
+            .iter()
+            .filter(|record| matches!(record.action, Some(ActionResult::Exited { code: Some(0), .. })))
+            .collect();
+        if !succeeded.is_empty() {
+            logger::info("The command succeeded in the following directories:");
+            for record in succeeded {
+                println!("{}", record.path.display());
+            }
+        }
+
+        let failed: Vec<&RepoRecord> = self
+            .repositories
+            .iter()
+            .filter(|record| {
+                record.error.is_some()
+                    || match record.action {
+                        Some(ActionResult::Failed { kind, .. }) => kind != FailureKind::AuthenticationRequired,
+                        Some(ActionResult::Exited { code, .. }) => code != Some(0),
+                        _ => false,
+                    }
+            })
+            .collect();
+        if !failed.is_empty() {
+            logger::error("The following directories failed:");
+            for record in failed {
+                match (&record.action, &record.error) {
+                    // Failures that didn't come from git have no output to explain them
+                    (Some(ActionResult::Failed { error, stderr, .. }), _) if stderr.is_empty() => {
+                        println!("{} ({})", record.path.display(), error);
+                    }
+                    (Some(ActionResult::Failed { kind, stderr, .. }), _) => {
+                        println!("{} ({})", record.path.display(), kind.description());
+                        for line in stderr.lines() {
+                            println!("    {}", line);
+                        }
+                    }
+                    (Some(ActionResult::Exited { code, .. }), _) => {
+                        println!("{} ({})", record.path.display(), exit_description(*code));
+                    }
+                    (_, Some(error)) => println!("{} ({})", record.path.display(), error),
+                    _ => {}
+                }
+            }
+        }
+
+        let authentication_required: Vec<&RepoRecord> = self
+            .repositories
+            .iter()
+            .filter(|record| {
+                matches!(
+                    record.action,
+                    Some(ActionResult::Failed { kind: FailureKind::AuthenticationRequired, .. })
+                )
+            })
+            .collect();
+        if !authentication_required.is_empty() {
+            logger::warning("The following directories need credentials that git could not prompt for:");
+            for record in authentication_required {
+                println!("{}", record.path.display());
+            }
+            logger::info(
+                "Run git once by hand in them to enter the credentials, or set up a credential helper or ssh-agent",
+            );
+        }
+
+        let needs_intervention: Vec<(&RepoRecord, &str)> = self
+            .repositories
+            .iter()
+            .filter_map(|record| match &record.action {
+                Some(ActionResult::NeedsIntervention { reason }) => Some((record, reason.as_str())),
+                _ => None,
+            })
+            .collect();
+        if !needs_intervention.is_empty() {
+            logger::warning("The following directories need manual intervention:");
+            for (record, reason) in needs_intervention {
+                println!("{} ({})", record.path.display(), reason);
+            }
+        }
+
+        let left_conflicted: Vec<(&RepoRecord, &str)> = self
+            .repositories
+            .iter()
+            .filter_map(|record| match &record.action {
+                Some(ActionResult::LeftConflicted { reason }) => Some((record, reason.as_str())),
+                _ => None,
+            })
+            .collect();
+        if !left_conflicted.is_empty() {
+            logger::warning("The following directories were left with conflicts to resolve by hand:");
+            for (record, reason) in left_conflicted {
+                println!("{} ({})", record.path.display(), reason);
+            }
+        }
+
+        let submodules_not_updated: Vec<(&RepoRecord, &str)> = self
+            .repositories
+            .iter()
+            .filter_map(|record| match &record.action {
+                Some(ActionResult::SubmodulesNotUpdated { reason }) => Some((record, reason.as_str())),
+                _ => None,
+            })
+            .collect();
+        if !submodules_not_updated.is_empty() {
+            logger::warning("The following directories were pulled, but their submodules could not be updated:");
+            for (record, reason) in submodules_not_updated {
+                println!("{} ({})", record.path.display(), reason);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn with_condition(&self, condition: Condition) -> Vec<(&RepoRecord, &RepoStatus)> {
+        self.repositories
+            .iter()
+            .filter(|record| record.conditions.contains(&condition))
+            .filter_map(|record| record.status.as_ref().map(|status| (record, status)))
+            .collect()
+    }
+}
+
+/// A human-readable form of an exit code, which is `None` when the process was killed by a signal.
+pub fn exit_description(code: Option<i32>) -> String {
+    match code {
+        Some(code) => format!("exit code {}", code),
+        None => "no exit code (killed by a signal)".to_string(),
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::{Path, PathBuf};
+use crate::cli::{RemoteOperationArgs, ReportFormat, StashCommand, StashPopArgs, StashPushArgs, StashReposArgs};
+use crate::commands::git_utilities::backend::error::LeftConflicted;
+use crate::commands::git_utilities::backend::{self, ChangeSelection};
+use crate::commands::git_utilities::filters::RepoFilter;
+use crate::commands::git_utilities::report::ActionResult;
+use crate::commands::git_utilities::utils::{collect_statuses, parallel_map, run_with_previewed_action};
+use crate::utils::logger;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::Write;
+
+pub fn run(args: &StashReposArgs) -> Result<()> {
+    match &args.command {
+        StashCommand::Push(args) => push(args),
+        StashCommand::Pop(args) => pop(args),
+        StashCommand::List(args) => list(args),
+    }
+}
+
+/// Stashes the local changes of every repository with changes to stash.
+fn push(args: &StashPushArgs) -> Result<()> {
+    let backend = backend::create(args.remote.backend);
+    let selection = ChangeSelection {
+        untracked: args.include_untracked,
+        ..ChangeSelection::default()
+    };
+
+    let preview = |path: &Path| backend.diffstat(path, &selection);
+
+    run_with_previewed_action(
+        &args.remote,
+        "Stash these changes?",
+        args.yes,
+        &preview,
+        |_path, status| {
+            let has_changes = status.has_tracked_changes() || (args.include_untracked && !status.untracked.is_empty());
+            has_changes && status.operation.is_none() && status.conflicted.is_empty()
+        },
+        |path, _status| {
+            if args.remote.dry_run {
+                return Ok(ActionResult::DryRun {
+                    command: format!("git -C {} {}", path.display(), push_args(args).join(" ")),
+                    commits: Vec::new(),
+                });
+            }
+
+            logger::debug(&format!("Stashing in '{}'... ", path.display()));
+            backend
+                .stash_push(&path, args.message.as_deref(), args.include_untracked)
+                .inspect_err(|err| {
+                    logger::error(&format!("Failed to stash in '{}': {:#}", path.display(), err));
+                })?;
+            Ok(ActionResult::Succeeded)
+        },
+    )
+}
+
+/// The arguments `GitBackend::stash_push` passes to git, for `--dry-run`.
+fn push_args(args: &StashPushArgs) -> Vec<String> {
+    let mut command = vec!["stash".to_string(), "push".to_string()];
+    if args.include_untracked {
+        command.push("--include-untracked".to_string());
+    }
+    if let Some(message) = &args.message {
+        command.push(format!("--message {:?}", message));
+    }
+    command
+}
+
+/// Restores the latest stash entry of every repository with a clean working tree, so the
+/// restored changes can't conflict with local ones.
+fn pop(args: &StashPopArgs) -> Result<()> {
+    let backend = backend::create(args.remote.backend);
+
+    let preview = |path: &Path| Ok(backend.stash_list(path)?.into_iter().take(1).collect());
+
+    run_with_previewed_action(
+        &args.remote,
+        "Restore these stash entries?",
+        args.yes,
+        &preview,
+        |_path, status| status.stashes > 0 && status.has_safe_worktree(args.remote.allow_untracked),
+        |path, _status| {
+            if args.remote.dry_run {
+                return Ok(ActionResult::DryRun {
+                    command: format!("git -C {} stash pop", path.display()),
+                    commits: backend.stash_list(&path)?.into_iter().take(1).collect(),
+                });
+            }
+
+            logger::debug(&format!("Restoring the stash in '{}'... ", path.display()));
+            backend.stash_pop(&path).inspect_err(|err| {
+                if err.is::<LeftConflicted>() {
+                    logger::warning(&format!("Stash pop in '{}' left conflicts to resolve: {}", path.display(), err));
+                } else {
+                    logger::error(&format!("Failed to restore the stash in '{}': {:#}", path.display(), err));
+                }
+            })?;
+            Ok(ActionResult::Succeeded)
+        },
+    )
+}
+
+/// The stash entries of a repository, as printed by `stash-repos list`.
+#[derive(Serialize)]
+struct StashListing {
+    path: PathBuf,
+    entries: Vec<String>,
+}
+
+/// Prints the stash entries of every repository that has some.
+fn list(args: &RemoteOperationArgs) -> Result<()> {
+    // Keep stdout clean for machine-readable reports
+    if args.format != ReportFormat::Text {
+        logger::init_stderr(true);
+    }
+
+    let backend = backend::create(args.backend);
+    let filter = RepoFilter::new(&args.filters)?;
+    let records = collect_statuses(args, &filter, backend.as_ref())?;
+    let stashed: Vec<PathBuf> = records
+        .into_iter()
+        .filter(|record| record.status.as_ref().is_some_and(|status| status.stashes > 0))
+        .map(|record| record.path)
+        .collect();
+
+    let listings = parallel_map(args.jobs, &stashed, |path| StashListing {
+        path: path.clone(),
+        entries: backend.stash_list(path).unwrap_or_else(|err| {
+            logger::error(&format!("Failed to list the stash in '{}': {:#}", path.display(), err));
+            Vec::new()
+        }),
+    });
+
+    let mut stdout = std::io::stdout().lock();
+    match args.format {
+        ReportFormat::Text => {
+            for listing in &listings {
+                println!("{}", listing.path.display());
+                for entry in &listing.entries {
+                    println!("    {}", entry);
+                }
+            }
+        }
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut stdout, &listings).context("Failed to write JSON report")?;
+            writeln!(stdout)?;
+        }
+        ReportFormat::Ndjson => {
+            for listing in &listings {
+                serde_json::to_writer(&mut stdout, listing).context("Failed to write NDJSON report")?;
+                writeln!(stdout)?;
+            }
+        }
+    }
+
+    Ok(())
+}
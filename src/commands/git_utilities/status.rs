@@ -0,0 +1,476 @@
+use serde::Serialize;
+
+/// Represents the status of a Git repository. Every condition is recorded, not just the first
+/// problem found, so commands can decide for themselves which ones they tolerate.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepoStatus {
+    /// The checked out branch, or `None` on a detached HEAD.
+    pub branch: Option<String>,
+    /// The upstream branch configured for `branch`, e.g. `origin/main`.
+    pub upstream: Option<String>,
+    /// Commits on `branch` that are not on its upstream. `None` if the upstream can't be resolved.
+    pub ahead: Option<usize>,
+    /// Commits on the upstream that are not on `branch`. `None` if the upstream can't be resolved.
+    pub behind: Option<usize>,
+    /// Remote-tracking branches with the same name as `branch`, e.g. `origin/main`. Only looked
+    /// up when there is no upstream, as candidates to link it to.
+    pub matching_remote_branches: Vec<String>,
+    /// Paths with changes in the index.
+    pub staged: Vec<String>,
+    /// Paths with changes in the working tree that are not staged.
+    pub modified: Vec<String>,
+    /// Paths that are not tracked by Git.
+    pub untracked: Vec<String>,
+    /// Paths with unresolved merge conflicts.
+    pub conflicted: Vec<String>,
+    /// Submodules that are not initialized, or whose checked out commit or content differs from
+    /// what the superproject records. Submodules without problems are left out.
+    pub submodules: Vec<SubmoduleStatus>,
+    /// The number of entries in the stash.
+    pub stashes: usize,
+    /// A multi-step operation that was started and not yet finished.
+    pub operation: Option<Operation>,
+}
+
+/// The problems of a single submodule, nested ones included.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SubmoduleStatus {
+    /// The path of the submodule, relative to the repository.
+    pub path: String,
+    /// Listed in `.gitmodules` but never checked out, e.g. after a clone without `--recursive`.
+    pub uninitialized: bool,
+    /// Another commit is checked out than the one the superproject records.
+    pub commit_changed: bool,
+    /// The submodule has changes or untracked files of its own.
+    pub dirty: bool,
+    /// Why the state of the submodule could not be read, if it couldn't.
+    pub error: Option<String>,
+}
+
+impl SubmoduleStatus {
+    /// A short, human-readable list of the problems, e.g. "not initialized".
+    pub fn problems(&self) -> String {
+        let problems = [
+            (self.uninitialized, "not initialized"),
+            (self.commit_changed, "at a different commit than recorded"),
+            (self.dirty, "dirty"),
+        ];
+        let mut problems: Vec<String> = problems
+            .iter()
+            .filter(|(applies, _)| *applies)
+            .map(|(_, problem)| problem.to_string())
+            .collect();
+        if let Some(error) = &self.error {
+            problems.push(format!("unreadable: {}", error));
+        }
+        problems.join(", ")
+    }
+}
+
+/// Multi-step Git operations that leave a repository in an intermediate state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+}
+
+/// A single condition that makes a repository not clean.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    OperationInProgress,
+    Conflicted,
+    Staged,
+    Modified,
+    Untracked,
+    SubmodulesOutOfSync,
+    Detached,
+    NoUpstream,
+    Diverged,
+    Ahead,
+    Behind,
+    Stashed,
+}
+
+impl Condition {
+    pub const ALL: [Condition; 12] = [
+        Condition::OperationInProgress,
+        Condition::Conflicted,
+        Condition::Staged,
+        Condition::Modified,
+        Condition::Untracked,
+        Condition::SubmodulesOutOfSync,
+        Condition::Detached,
+        Condition::NoUpstream,
+        Condition::Diverged,
+        Condition::Ahead,
+        Condition::Behind,
+        Condition::Stashed,
+    ];
+
+    /// A short name for this condition, e.g. in the transitions shown by `check-repos --watch`.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Condition::OperationInProgress => "operation in progress",
+            Condition::Conflicted => "unresolved conflicts",
+            Condition::Staged => "staged changes",
+            Condition::Modified => "uncommitted changes",
+            Condition::Untracked => "untracked files",
+            Condition::SubmodulesOutOfSync => "submodule problems",
+            Condition::Detached => "detached HEAD",
+            Condition::NoUpstream => "no upstream branch",
+            Condition::Diverged => "diverged from upstream",
+            Condition::Ahead => "ahead of upstream",
+            Condition::Behind => "behind upstream",
+            Condition::Stashed => "stashed changes",
+        }
+    }
+
+    /// The heading used for this condition in the text report.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Condition::OperationInProgress => "have a merge, rebase or similar operation in progress",
+            Condition::Conflicted => "contain unresolved conflicts",
+            Condition::Staged => "contain staged changes",
+            Condition::Modified => "contain uncommitted changes",
+            Condition::Untracked => "contain untracked files",
+            Condition::SubmodulesOutOfSync => "have submodules that are not initialized, out of sync or dirty",
+            Condition::Detached => "are in a detached HEAD state",
+            Condition::NoUpstream => "do not have an upstream branch set",
+            Condition::Diverged => "have diverged from their upstream branch",
+            Condition::Ahead => "contain changes that were committed but not yet pushed",
+            Condition::Behind => "are behind their upstream branch",
+            Condition::Stashed => "contain stashed changes",
+        }
+    }
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Merge => "merge",
+            Operation::Rebase => "rebase",
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+            Operation::Bisect => "bisect",
+        }
+    }
+}
+
+impl RepoStatus {
+    pub fn is_detached(&self) -> bool {
+        self.branch.is_none()
+    }
+
+    /// Whether the only changes in the working tree are untracked files.
+    pub fn is_untracked_only(&self) -> bool {
+        !self.untracked.is_empty() && !self.has_tracked_changes()
+    }
+
+    /// Whether tracked files have staged, unstaged or conflicting changes.
+    pub fn has_tracked_changes(&self) -> bool {
+        !self.staged.is_empty() || !self.modified.is_empty() || !self.conflicted.is_empty()
+    }
+
+    /// The entry of the submodule at `path`, added if it isn't listed yet.
+    pub fn submodule_mut(&mut self, path: &str) -> &mut SubmoduleStatus {
+        let index = match self.submodules.iter().position(|submodule| submodule.path == path) {
+            Some(index) => index,
+            None => {
+                self.submodules.push(SubmoduleStatus {
+                    path: path.to_string(),
+                    ..SubmoduleStatus::default()
+                });
+                self.submodules.len() - 1
+            }
+        };
+        &mut self.submodules[index]
+    }
+
+    pub fn is_diverged(&self) -> bool {
+        self.ahead.unwrap_or(0) > 0 && self.behind.unwrap_or(0) > 0
+    }
+
+    /// Every condition that currently applies to the repository.
+    pub fn conditions(&self) -> Vec<Condition> {
+        let mut conditions = Vec::new();
+
+        if self.operation.is_some() {
+            conditions.push(Condition::OperationInProgress);
+        }
+        if !self.conflicted.is_empty() {
+            conditions.push(Condition::Conflicted);
+        }
+        if !self.staged.is_empty() {
+            conditions.push(Condition::Staged);
+        }
+        if !self.modified.is_empty() {
+            conditions.push(Condition::Modified);
+        }
+        if !self.untracked.is_empty() {
+            conditions.push(Condition::Untracked);
+        }
+        if !self.submodules.is_empty() {
+            conditions.push(Condition::SubmodulesOutOfSync);
+        }
+        if self.is_detached() {
+            conditions.push(Condition::Detached);
+        } else if self.ahead.is_none() {
+            conditions.push(Condition::NoUpstream);
+        }
+        if self.is_diverged() {
+            conditions.push(Condition::Diverged);
+        } else if self.ahead.unwrap_or(0) > 0 {
+            conditions.push(Condition::Ahead);
+        } else if self.behind.unwrap_or(0) > 0 {
+            conditions.push(Condition::Behind);
+        }
+        if self.stashes > 0 {
+            conditions.push(Condition::Stashed);
+        }
+
+        conditions
+    }
+
+    /// Whether the working tree can safely be updated: no tracked changes, no operation in
+    /// progress, and untracked files only if `allow_untracked` is set.
+    pub fn has_safe_worktree(&self, allow_untracked: bool) -> bool {
+        let is_pristine = !self.has_tracked_changes() && self.untracked.is_empty();
+        self.operation.is_none() && (is_pristine || (allow_untracked && self.is_untracked_only()))
+    }
+
+    /// A short, human-readable description of the details behind `condition`.
+    pub fn detail(&self, condition: Condition) -> Option<String> {
+        match condition {
+            Condition::OperationInProgress => self.operation.map(|op| format!("{} in progress", op.as_str())),
+            Condition::Conflicted => Some(count(self.conflicted.len(), "conflicted file")),
+            Condition::Staged => Some(count(self.staged.len(), "staged file")),
+            Condition::Modified => Some(count(self.modified.len(), "modified file")),
+            Condition::Untracked => Some(count(self.untracked.len(), "untracked file")),
+            Condition::SubmodulesOutOfSync => Some(count(self.submodules.len(), "submodule")),
+            Condition::Diverged => Some(format!(
+                "ahead by {}, behind by {}",
+                self.ahead.unwrap_or(0),
+                self.behind.unwrap_or(0)
+            )),
+            Condition::Ahead => self.ahead.map(|n| format!("ahead by {}", n)),
+            Condition::Behind => self.behind.map(|n| format!("behind by {}", n)),
+            Condition::Stashed => Some(count(self.stashes, "stash")),
+            Condition::Detached | Condition::NoUpstream => None,
+        }
+    }
+}
+
+fn count(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("{} {}", n, noun)
+    } else {
+        format!("{} {}s", n, noun)
+    }
+}
+
+/// Parses the output of `git status --porcelain=v2 --branch -z`.
+pub fn parse_porcelain_v2(output: &str) -> RepoStatus {
+    let mut status = RepoStatus::default();
+    let mut fields = output.split('\0').filter(|field| !field.is_empty());
+
+    while let Some(field) = fields.next() {
+        if let Some(header) = field.strip_prefix("# ") {
+            if let Some(head) = header.strip_prefix("branch.head ") {
+                if head != "(detached)" {
+                    status.branch = Some(head.to_string());
+                }
+            } else if let Some(upstream) = header.strip_prefix("branch.upstream ") {
+                status.upstream = Some(upstream.to_string());
+            } else if let Some(counts) = header.strip_prefix("branch.ab ") {
+                let mut counts = counts.split(' ');
+                status.ahead = counts.next().and_then(|c| c.trim_start_matches('+').parse().ok());
+                status.behind = counts.next().and_then(|c| c.trim_start_matches('-').parse().ok());
+            }
+            continue;
+        }
+
+        // Changed entries carry a fixed number of space-separated fields before the path:
+        // "1 XY sub ... path", "2 XY sub ... path" (followed by the original path) and "u XY sub ... path"
+        let (parts, path) = match field.as_bytes().first() {
+            Some(b'1') => (field.splitn(9, ' ').collect::<Vec<_>>(), 8),
+            Some(b'2') => {
+                fields.next();
+                (field.splitn(10, ' ').collect::<Vec<_>>(), 9)
+            }
+            Some(b'u') => {
+                if let Some(path) = field.splitn(11, ' ').nth(10) {
+                    status.conflicted.push(path.to_string());
+                }
+                continue;
+            }
+            Some(b'?') => {
+                status.untracked.push(field[2..].to_string());
+                continue;
+            }
+            _ => continue,
+        };
+
+        let (Some(xy), Some(sub), Some(path)) = (parts.get(1), parts.get(2), parts.get(path)) else {
+            continue;
+        };
+        let path = path.to_string();
+
+        // Submodule entries ("S<c><m><u>") are tracked separately from regular files
+        if let Some(flags) = sub.strip_prefix('S') {
+            let mut flags = flags.chars();
+            let submodule = status.submodule_mut(&path);
+            submodule.commit_changed = flags.next() == Some('C');
+            submodule.dirty = flags.any(|flag| flag != '.');
+            continue;
+        }
+
+        let mut xy = xy.chars();
+        if xy.next().is_some_and(|x| x != '.') {
+            status.staged.push(path.clone());
+        }
+        if xy.next().is_some_and(|y| y != '.') {
+            status.modified.push(path);
+        }
+    }
+
+    status
+}
+
+/// Records the submodules that `git submodule status --recursive` reports as never initialized
+/// (`-`) or at another commit than the superproject records (`+`) in `status`.
+pub fn parse_submodule_status(output: &str, status: &mut RepoStatus) {
+    for line in output.lines() {
+        let (Some(state), Some((_, rest))) = (line.chars().next(), line.get(1..).and_then(|line| line.split_once(' ')))
+        else {
+            continue;
+        };
+        // Initialized submodules are followed by the `git describe` of their commit
+        let submodule_path = match rest.rsplit_once(" (") {
+            Some((submodule_path, _)) if rest.ends_with(')') => submodule_path,
+            _ => rest,
+        };
+        match state {
+            '-' => status.submodule_mut(submodule_path).uninitialized = true,
+            '+' => status.submodule_mut(submodule_path).commit_changed = true,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_branch_headers() {
+        let status = parse_porcelain_v2(
+            "# branch.oid 1234\0# branch.head main\0# branch.upstream origin/main\0# branch.ab +2 -3\0",
+        );
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.upstream.as_deref(), Some("origin/main"));
+        assert_eq!(status.ahead, Some(2));
+        assert_eq!(status.behind, Some(3));
+    }
+
+    #[test]
+    fn detached_head_and_missing_upstream() {
+        let status = parse_porcelain_v2("# branch.oid 1234\0# branch.head (detached)\0");
+        assert_eq!(status.branch, None);
+        assert_eq!(status.upstream, None);
+        assert_eq!(status.ahead, None);
+        assert_eq!(status.behind, None);
+    }
+
+    #[test]
+    fn sorts_changed_entries_by_kind() {
+        let output = [
+            "# branch.head main",
+            "1 M. N... 100644 100644 100644 aaaa bbbb staged.rs",
+            "1 .M N... 100644 100644 100644 aaaa aaaa modified.rs",
+            "1 MM N... 100644 100644 100644 aaaa bbbb both.rs",
+            "2 R. N... 100644 100644 100644 aaaa aaaa R100 new name.rs",
+            "old name.rs",
+            "u UU N... 100644 100644 100644 100644 aaaa bbbb cccc conflicted.rs",
+            "? untracked file.txt",
+            "! ignored.log",
+        ]
+        .join("\0");
+        let status = parse_porcelain_v2(&output);
+
+        assert_eq!(status.staged, ["staged.rs", "both.rs", "new name.rs"]);
+        assert_eq!(status.modified, ["modified.rs", "both.rs"]);
+        assert_eq!(status.conflicted, ["conflicted.rs"]);
+        assert_eq!(status.untracked, ["untracked file.txt"]);
+    }
+
+    #[test]
+    fn clean_repository() {
+        let status = parse_porcelain_v2("# branch.oid 1234\0# branch.head main\0");
+        assert!(status.conditions().contains(&Condition::NoUpstream));
+        assert!(!status.has_tracked_changes());
+        assert!(status.untracked.is_empty());
+    }
+
+    #[test]
+    fn submodule_entries_of_porcelain_v2() {
+        let output = [
+            "# branch.head main",
+            "1 .M SC.. 160000 160000 160000 aaaa aaaa vendor/lib",
+            "1 .M S.MU 160000 160000 160000 aaaa aaaa vendor/dirty lib",
+        ]
+        .join("\0");
+        let status = parse_porcelain_v2(&output);
+
+        assert!(status.modified.is_empty());
+        let [moved, dirty] = &status.submodules[..] else {
+            panic!("{:?}", status.submodules);
+        };
+        assert_eq!((moved.path.as_str(), moved.commit_changed, moved.dirty), ("vendor/lib", true, false));
+        assert_eq!((dirty.path.as_str(), dirty.commit_changed, dirty.dirty), ("vendor/dirty lib", false, true));
+    }
+
+    #[test]
+    fn parses_submodule_status() {
+        let output = concat!(
+            " 1111111111111111111111111111111111111111 vendor/ok (v1.0)\n",
+            "-2222222222222222222222222222222222222222 vendor/uninitialized\n",
+            "+3333333333333333333333333333333333333333 vendor/moved (heads/main)\n",
+            "+4444444444444444444444444444444444444444 vendor/with (parens) (v2.0-1-g4444444)\n",
+            "U5555555555555555555555555555555555555555 vendor/conflicted\n",
+        );
+        let mut status = RepoStatus::default();
+        parse_submodule_status(output, &mut status);
+
+        let problems: Vec<(&str, String)> = status
+            .submodules
+            .iter()
+            .map(|submodule| (submodule.path.as_str(), submodule.problems()))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                ("vendor/uninitialized", "not initialized".to_string()),
+                ("vendor/moved", "at a different commit than recorded".to_string()),
+                ("vendor/with (parens)", "at a different commit than recorded".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_submodule_problems() {
+        let mut status = RepoStatus::default();
+        status.submodule_mut("vendor/lib").dirty = true;
+        parse_submodule_status("+3333333333333333333333333333333333333333 vendor/lib (heads/main)\n", &mut status);
+        status.submodule_mut("vendor/broken").error = Some("invalid config".to_string());
+
+        assert_eq!(status.submodules.len(), 2);
+        assert_eq!(status.submodules[0].problems(), "at a different commit than recorded, dirty");
+        assert_eq!(status.submodules[1].problems(), "unreadable: invalid config");
+        assert!(status.conditions().contains(&Condition::SubmodulesOutOfSync));
+    }
+}
@@ -0,0 +1,165 @@
+use crate::cli::{PullReposArgs, RemoteOperationArgs, ReportFormat, SyncArgs};
+use crate::commands::git_utilities::backend::{self, GitBackend};
+use crate::commands::git_utilities::discovery::{discover_repositories, repo_kind};
+use crate::commands::git_utilities::filters::RepoFilter;
+use crate::commands::git_utilities::manifest::{Manifest, ManifestRepo};
+use crate::commands::git_utilities::pull_repos;
+use crate::commands::git_utilities::utils::{parallel_map, run_check};
+use crate::utils::logger;
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub fn run(args: &SyncArgs) -> Result<()> {
+    // Keep stdout clean for machine-readable reports
+    if args.format != ReportFormat::Text {
+        logger::init_stderr(true);
+    }
+
+    let manifest = Manifest::load(&args.manifest)?;
+    let repositories = manifest.select(&args.groups);
+    if repositories.is_empty() {
+        logger::warning("No repository in the manifest matches the selected groups.");
+        return Ok(());
+    }
+
+    let backend = backend::create(args.backend);
+
+    // 1. Clone the repositories that are missing
+    let missing: Vec<&ManifestRepo> = repositories
+        .iter()
+        .copied()
+        .filter(|repo| !repo.path.exists())
+        .collect();
+
+    let mut clone_failures = 0;
+    if args.dry_run {
+        for repo in &missing {
+            logger::info(&format!("Would clone '{}' into '{}'", repo.url, repo.path.display()));
+        }
+    } else {
+        let results = parallel_map(args.jobs, &missing, |repo| {
+            clone(backend.as_ref(), repo, Duration::from_secs(args.timeout))
+        });
+        clone_failures = results.into_iter().filter(|cloned| !cloned).count();
+    }
+
+    // 2. Paths that exist but hold no repository can be neither cloned nor checked
+    let (present, not_repositories): (Vec<PathBuf>, Vec<PathBuf>) = repositories
+        .iter()
+        .map(|repo| repo.path.clone())
+        .filter(|path| path.exists())
+        .partition(|path| repo_kind(path).is_some());
+    for path in &not_repositories {
+        logger::error(&format!(
+            "'{}' is listed in the manifest, but exists and is not a git repository",
+            path.display()
+        ));
+    }
+
+    // 3. Point out the repositories in the workspace that nobody declared
+    let remote = RemoteOperationArgs {
+        directories: present,
+        max_depth: 0,
+        jobs: args.jobs,
+        fetch: args.fetch,
+        timeout: args.timeout,
+        backend: args.backend,
+        dry_run: args.dry_run,
+        format: args.format,
+        ..RemoteOperationArgs::default()
+    };
+    report_unmanaged(&manifest, &remote)?;
+
+    // 4. Check or pull every repository of the manifest that is present
+    let result = if remote.directories.is_empty() {
+        Ok(())
+    } else if args.pull {
+        pull_repos::run(&PullReposArgs {
+            remote,
+            strategy: args.strategy,
+            autostash: false,
+            update_submodules: false,
+        })
+    } else {
+        run_check(&remote)
+    };
+
+    // Report every kind of failure, not only the first one
+    let mut errors = Vec::new();
+    if let Err(err) = result {
+        errors.push(format!("{:#}", err));
+    }
+    if clone_failures > 0 {
+        errors.push(format!(
+            "{} of {} missing repositories could not be cloned",
+            clone_failures,
+            missing.len()
+        ));
+    }
+    if !not_repositories.is_empty() {
+        errors.push(format!(
+            "{} paths of the manifest are not git repositories",
+            not_repositories.len()
+        ));
+    }
+    if !errors.is_empty() {
+        bail!("{}", errors.join("; "));
+    }
+
+    Ok(())
+}
+
+/// Clones a single repository of the manifest. Returns whether it succeeded.
+fn clone(backend: &dyn GitBackend, repo: &ManifestRepo, timeout: Duration) -> bool {
+    logger::info(&format!("Cloning '{}' into '{}'...", repo.url, repo.path.display()));
+
+    match backend.clone_repo(&repo.url, &repo.path, repo.branch.as_deref(), timeout) {
+        Ok(()) => true,
+        Err(err) => {
+            logger::error(&format!("Failed to clone '{}': {:#}", repo.url, err));
+            false
+        }
+    }
+}
+
+/// Warns about repositories under the manifest's root that it doesn't list, searching as deep
+/// as the deepest repository it does list.
+fn report_unmanaged(manifest: &Manifest, args: &RemoteOperationArgs) -> Result<()> {
+    let managed: HashSet<PathBuf> = manifest
+        .repositories
+        .iter()
+        .filter_map(|repo| repo.path.canonicalize().ok())
+        .collect();
+
+    let max_depth = manifest
+        .repositories
+        .iter()
+        .filter_map(|repo| repo.path.strip_prefix(&manifest.root).ok())
+        .map(|path| path.components().count())
+        .max()
+        .unwrap_or(1);
+
+    let workspace = RemoteOperationArgs {
+        directories: vec![manifest.root.clone()],
+        max_depth,
+        ..args.clone()
+    };
+
+    let filter = RepoFilter::new(&workspace.filters)?;
+    let unmanaged: Vec<PathBuf> = discover_repositories(&workspace, &filter)?
+        .into_iter()
+        .map(|repo| repo.path)
+        .filter(|path| path.canonicalize().is_ok_and(|path| !managed.contains(&path)))
+        .collect();
+
+    if !unmanaged.is_empty() {
+        logger::warning("The following repositories are not listed in the manifest:");
+        for path in unmanaged {
+            logger::warning(&path.display().to_string());
+        }
+    }
+
+    Ok(())
+}
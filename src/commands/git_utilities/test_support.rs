@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Runs `git <args>` in `dir` and returns its trimmed stdout, panicking if it fails. The user's
+/// and the system's git config are left out, so the fixtures look the same everywhere.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .output()
+        .expect("failed to run git");
+    assert!(
+        output.status.success(),
+        "git {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Like `git`, for commands that are expected to fail, e.g. a merge that stops on conflicts.
+pub fn git_fails(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .output()
+        .expect("failed to run git")
+        .status;
+    assert!(!status.success(), "git {} unexpectedly succeeded", args.join(" "));
+}
+
+/// A temporary directory holding fixture repositories, removed when dropped.
+pub struct Fixture {
+    dir: TempDir,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Self {
+            dir: TempDir::new().expect("failed to create a temporary directory"),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// A new repository at `name` on branch `main`, with a commit of a `README.md`.
+    pub fn repo(&self, name: &str) -> PathBuf {
+        let path = self.path().join(name);
+        fs::create_dir_all(&path).unwrap();
+        init(&path);
+        commit_file(&path, "README.md", "hello\n", "Initial commit");
+        path
+    }
+
+    /// A bare repository at `name` to use as a remote, with a clone of it at `clone` whose `main`
+    /// tracks `origin/main`.
+    pub fn repo_with_remote(&self, name: &str, clone: &str) -> (PathBuf, PathBuf) {
+        let seed = self.repo(&format!("{}-seed", name));
+        let remote = self.path().join(name);
+        git(self.path(), &["clone", "-q", "--bare", seed.to_str().unwrap(), remote.to_str().unwrap()]);
+
+        let local = self.path().join(clone);
+        git(self.path(), &["clone", "-q", remote.to_str().unwrap(), local.to_str().unwrap()]);
+        configure(&local);
+        (remote, local)
+    }
+
+    /// Commits `content` to `file` on the `main` branch of the bare repository `remote`, as
+    /// someone else would.
+    pub fn commit_to_remote(&self, remote: &Path, file: &str, content: &str) {
+        let other = TempDir::new_in(self.path()).unwrap();
+        git(other.path(), &["clone", "-q", remote.to_str().unwrap(), "."]);
+        configure(other.path());
+        commit_file(other.path(), file, content, "Their commit");
+        git(other.path(), &["push", "-q"]);
+    }
+
+    /// Repositories in the states `status` has to tell apart, by name: clean, dirty, staged,
+    /// conflicted, detached, ahead and behind, and stashed.
+    pub fn status_scenarios(&self) -> Vec<(&'static str, PathBuf)> {
+        let (_, clean) = self.repo_with_remote("clean.git", "clean");
+
+        let dirty = self.repo("dirty");
+        write(&dirty, "README.md", "changed\n");
+        write(&dirty, "notes/new.txt", "new\n");
+
+        let staged = self.repo("staged");
+        write(&staged, "staged.txt", "staged\n");
+        git(&staged, &["add", "staged.txt"]);
+        write(&staged, "README.md", "staged\n");
+        git(&staged, &["add", "README.md"]);
+        write(&staged, "README.md", "staged, then changed\n");
+
+        let conflicted = self.repo("conflicted");
+        git(&conflicted, &["checkout", "-q", "-b", "other"]);
+        commit_file(&conflicted, "README.md", "theirs\n", "Change on other");
+        git(&conflicted, &["checkout", "-q", "main"]);
+        commit_file(&conflicted, "README.md", "ours\n", "Change on main");
+        git_fails(&conflicted, &["merge", "-q", "other"]);
+
+        let detached = self.repo("detached");
+        commit_file(&detached, "second.txt", "second\n", "Second commit");
+        git(&detached, &["checkout", "-q", "--detach", "HEAD~1"]);
+
+        let (remote, ahead_behind) = self.repo_with_remote("ahead-behind.git", "ahead-behind");
+        self.commit_to_remote(&remote, "theirs.txt", "theirs\n");
+        commit_file(&ahead_behind, "ours.txt", "ours\n", "Our first commit");
+        commit_file(&ahead_behind, "ours.txt", "ours, again\n", "Our second commit");
+        git(&ahead_behind, &["fetch", "-q"]);
+
+        let stashed = self.repo("stashed");
+        write(&stashed, "README.md", "stashed\n");
+        git(&stashed, &["stash", "-q"]);
+
+        vec![
+            ("clean", clean),
+            ("dirty", dirty),
+            ("staged", staged),
+            ("conflicted", conflicted),
+            ("detached", detached),
+            ("ahead-behind", ahead_behind),
+            ("stashed", stashed),
+        ]
+    }
+}
+
+/// Creates an empty repository at `path`, on branch `main`.
+pub fn init(path: &Path) {
+    git(path, &["init", "-q", "-b", "main"]);
+    configure(path);
+}
+
+/// Writes `content` to `file` in the repository at `path`, and commits it.
+pub fn commit_file(path: &Path, file: &str, content: &str, message: &str) {
+    write(path, file, content);
+    git(path, &["add", "--", file]);
+    git(path, &["commit", "-q", "-m", message]);
+}
+
+/// Writes `content` to `file` in the work tree at `path`, creating its parent directories.
+pub fn write(path: &Path, file: &str, content: &str) {
+    let file = path.join(file);
+    fs::create_dir_all(file.parent().unwrap()).unwrap();
+    fs::write(file, content).unwrap();
+}
+
+/// Gives the repository an identity to commit with, so the commands under test, which do read
+/// the user's config, don't depend on it.
+fn configure(path: &Path) {
+    git(path, &["config", "user.name", "Fixture"]);
+    git(path, &["config", "user.email", "fixture@example.com"]);
+    git(path, &["config", "commit.gpgsign", "false"]);
+}
@@ -1,167 +1,401 @@
-use crate::cli::RemoteOperationArgs;
-use crate::utils::logger;
-use anyhow::{Context, Result};
-use std::fs;
+use crate::cli::{RemoteOperationArgs, ReportFormat};
+use crate::commands::git_utilities::discovery::{discover_repositories, RepoKind};
+use crate::commands::git_utilities::filters::RepoFilter;
+use crate::commands::git_utilities::process::TimedOut;
+use crate::commands::git_utilities::report::{ActionResult, RepoRecord, Report};
+use crate::commands::git_utilities::backend::error::{FailureKind, GitFailure, LeftConflicted, NeedsIntervention};
+use crate::commands::git_utilities::backend::{self, GitBackend};
+use crate::commands::git_utilities::status::RepoStatus;
+use crate::utils::{logger, prompt};
+use anyhow::{bail, Result};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-
-/// Represents the status of a Git repository.
-pub enum RepoStatus {
-    Uncommitted,
-    NoUpstream,
-    NotPushed,
-    Ok,
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// Checks every repository inside `args.directories` and reports their status, without acting on them.
+pub fn run_check(args: &RemoteOperationArgs) -> Result<()> {
+    run_repositories::<fn(&Path, &RepoStatus) -> bool, fn(PathBuf, &RepoStatus) -> Result<ActionResult>>(
+        args, None, None,
+    )
 }
 
-pub fn run_with_action<F>(args: &RemoteOperationArgs, mut on_ok: F) -> anyhow::Result<(), anyhow::Error>
+/// Checks every repository inside `args.directories`, runs `on_ok` on the ones `is_eligible`
+/// accepts and reports the results. `on_ok` returns the entry recorded in the report.
+pub fn run_with_action<E, F>(
+    args: &RemoteOperationArgs,
+    is_eligible: E,
+    on_ok: F,
+) -> anyhow::Result<(), anyhow::Error>
 where
-    F: FnMut(PathBuf) -> Result<(), anyhow::Error>,
+    E: Fn(&RepoStatus) -> bool + Sync,
+    F: Fn(PathBuf, &RepoStatus) -> Result<ActionResult, anyhow::Error> + Sync,
 {
-    let mut uncommitted = Vec::new();
-    let mut no_upstream = Vec::new();
-    let mut not_pushed = Vec::new();
-
-    // 1. Navigate through each of the directories passed as arguments
-    for dir in &args.directories {
-        // Confirm that the path is a valid direct
-        if !dir.is_dir() {
-            logger::error(&format!("Not a directory: '{}'", dir.display()));
-            continue;
-        }
+    let is_eligible = |_: &Path, status: &RepoStatus| is_eligible(status);
+    run_repositories(args, Some((&is_eligible, &on_ok)), None)
+}
 
-        // 2. Iterate over each directory inside the path
-        for entry in fs::read_dir(dir)
-            .with_context(|| format!("Failed to read directory {}", dir.display()))?
-        {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.is_dir() {
-                logger::debug(&format!("Checking '{}'", path.display()));
-                // 3. Get directory status
-                match check_repo_status(&path)? {
-                    Some(RepoStatus::Uncommitted) => uncommitted.push(path),
-                    Some(RepoStatus::NoUpstream) => no_upstream.push(path),
-                    Some(RepoStatus::NotPushed) => not_pushed.push(path),
-                    Some(RepoStatus::Ok) => {
-                        on_ok(path)?;
-                    }
-                    None => {
-                        logger::warning(&format!("Not a git repository: '{}'", path.display()));
+/// Like `run_with_action`, but lists the eligible repositories and asks the user to confirm
+/// `question` before acting on any of them. `assume_yes` skips the question.
+pub fn run_with_confirmed_action<E, F>(
+    args: &RemoteOperationArgs,
+    question: &str,
+    assume_yes: bool,
+    is_eligible: E,
+    on_ok: F,
+) -> Result<()>
+where
+    E: Fn(&RepoStatus) -> bool + Sync,
+    F: Fn(PathBuf, &RepoStatus) -> Result<ActionResult, anyhow::Error> + Sync,
+{
+    let confirmation = Confirmation { question, preview: None };
+    let confirmation = (!assume_yes && !args.dry_run).then_some(confirmation);
+    let is_eligible = |_: &Path, status: &RepoStatus| is_eligible(status);
+    run_repositories(args, Some((&is_eligible, &on_ok)), confirmation)
+}
+
+/// Like `run_with_confirmed_action`, but also shows the lines returned by `preview` under each
+/// repository, e.g. the changes the action will affect. `is_eligible` is given the path of the
+/// repository too, for what its status can't tell.
+pub fn run_with_previewed_action<E, F>(
+    args: &RemoteOperationArgs,
+    question: &str,
+    assume_yes: bool,
+    preview: &Preview<'_>,
+    is_eligible: E,
+    on_ok: F,
+) -> Result<()>
+where
+    E: Fn(&Path, &RepoStatus) -> bool + Sync,
+    F: Fn(PathBuf, &RepoStatus) -> Result<ActionResult, anyhow::Error> + Sync,
+{
+    let confirmation = Confirmation {
+        question,
+        preview: Some(preview),
+    };
+    let confirmation = (!assume_yes && !args.dry_run).then_some(confirmation);
+    run_repositories(args, Some((&is_eligible, &on_ok)), confirmation)
+}
+
+/// Describes what an action will do in the repository at the given path, one line per item.
+pub type Preview<'a> = dyn Fn(&Path) -> Result<Vec<String>> + Sync + 'a;
+
+/// What to show and ask the user before acting on the eligible repositories.
+struct Confirmation<'a> {
+    question: &'a str,
+    preview: Option<&'a Preview<'a>>,
+}
+
+fn run_repositories<E, F>(
+    args: &RemoteOperationArgs,
+    action: Option<(&E, &F)>,
+    confirmation: Option<Confirmation>,
+) -> Result<()>
+where
+    E: Fn(&Path, &RepoStatus) -> bool + Sync,
+    F: Fn(PathBuf, &RepoStatus) -> Result<ActionResult, anyhow::Error> + Sync,
+{
+    // Keep stdout clean for machine-readable reports
+    if args.format != ReportFormat::Text {
+        logger::init_stderr(true);
+    }
+
+    let backend = backend::create(args.backend);
+    let filter = RepoFilter::new(&args.filters)?;
+
+    // 1. Find and check every repository inside the directories passed as arguments
+    let mut records = collect_statuses(args, &filter, backend.as_ref())?;
+
+    // 2. Act on the eligible repositories, once the user agreed to it if they have to
+    if let Some((is_eligible, on_ok)) = action {
+        let eligible: Vec<usize> = records
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.status.as_ref().is_some_and(|status| is_eligible(&record.path, status)))
+            .map(|(index, _)| index)
+            .collect();
+
+        if let Some(confirmation) = confirmation {
+            if !eligible.is_empty() {
+                logger::info("The following directories will be affected:");
+                for index in &eligible {
+                    let path = &records[*index].path;
+                    logger::info(&path.display().to_string());
+                    match confirmation.preview.map(|preview| preview(path)) {
+                        Some(Ok(lines)) => lines.iter().for_each(|line| logger::output(&format!("    {}", line))),
+                        Some(Err(err)) => logger::warning(&format!("Failed to preview '{}': {:#}", path.display(), err)),
+                        None => {}
                     }
                 }
+                if !prompt::confirm(confirmation.question)? {
+                    logger::info("Aborted, no repository was changed.");
+                    return Ok(());
+                }
+            }
+        }
+
+        let results = parallel_map(args.jobs, &eligible, |index| {
+            let record = &records[*index];
+            let status = record.status.as_ref().expect("eligible repositories have a status");
+            run_action(backend.as_ref(), &record.path, status, on_ok)
+        });
+
+        for (index, (result, status)) in eligible.into_iter().zip(results) {
+            let record = &mut records[index];
+            record.action = Some(result);
+            if let Some(status) = status {
+                record.conditions = status.conditions();
+                record.status = Some(status);
             }
         }
     }
 
-    // 4. Report to the user the results
-    print_report(
-        &uncommitted,
-        &no_upstream,
-        &not_pushed,
-    )?;
+    // 3. Report to the user the results
+    let report = Report::new(records);
+    report.print(args.format)?;
+
+    // 4. Fail the process on partial failure, so scripts and CI jobs can detect it
+    let summary = &report.summary;
+    let unsuccessful = summary.failed
+        + summary.needs_intervention
+        + summary.left_conflicted
+        + summary.submodules_not_updated
+        + summary.authentication_required;
+    if unsuccessful > 0 {
+        bail!(
+            "{} of {} repositories could not be processed",
+            unsuccessful,
+            summary.total
+        );
+    }
 
     Ok(())
 }
 
-/// Checks a single directory to see if it's a Git repo and what its status is.
-fn check_repo_status(path: &Path) -> Result<Option<RepoStatus>> {
-    // 1. Check if it's a git repository
-    let is_repo = Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("rev-parse")
-        .arg("--is-inside-work-tree")
-        .output()?
-        .status
-        .success();
-
-    if !is_repo {
-        return Ok(None);
-    }
+/// Finds every repository inside `args.directories` and checks its status, in discovery order.
+/// Repositories filtered out by `filter`, built from `args.filters`, are left out.
+pub fn collect_statuses(
+    args: &RemoteOperationArgs,
+    filter: &RepoFilter,
+    backend: &dyn GitBackend,
+) -> Result<Vec<RepoRecord>> {
+    let repositories: Vec<PathBuf> = discover_repositories(args, filter)?
+        .into_iter()
+        .filter_map(|repo| {
+            if repo.kind == RepoKind::Bare {
+                logger::warning(&format!("Skipping bare repository: '{}'", repo.path.display()));
+                None
+            } else {
+                Some(repo.path)
+            }
+        })
+        .collect();
+
+    let mut records = parallel_map(args.jobs, &repositories, |path| check_repository(args, backend, path));
+
+    // `--status` needs the status, so it is the only filter applied after checking
+    records.retain(|record| record.status.as_ref().is_none_or(|status| filter.matches_status(status)));
+    Ok(records)
+}
+
+/// Runs `f` on every item using a pool of `jobs` worker threads, and returns the results in
+/// the order of `items`. The log lines of each call are buffered and printed as one block
+/// once it is done, so they don't interleave.
+pub fn parallel_map<T, R, F>(jobs: usize, items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next_index = AtomicUsize::new(0);
+    let results: Mutex<Vec<(usize, R)>> = Mutex::new(Vec::with_capacity(items.len()));
+    let workers = jobs.clamp(1, items.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+
+                let (result, output) = logger::capture(|| f(item));
+                logger::flush(&output);
+                results
+                    .lock()
+                    .expect("results lock poisoned")
+                    .push((index, result));
+            });
+        }
+    });
+
+    // Sort the results back into input order so the report is deterministic
+    let mut results = results.into_inner().expect("results lock poisoned");
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
 
-    // 2. Check for uncommitted changes
-    let status_output = Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("status")
-        .arg("--porcelain")
-        .output()?;
-    if !status_output.stdout.is_empty() {
-        return Ok(Some(RepoStatus::Uncommitted));
+/// Formats an age in seconds the way people say it, e.g. "3 days ago".
+pub fn relative_time(seconds: i64) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+
+    let (amount, unit) = match seconds {
+        s if s < MINUTE => return "just now".to_string(),
+        s if s < HOUR => (s / MINUTE, "minute"),
+        s if s < DAY => (s / HOUR, "hour"),
+        s if s < 30 * DAY => (s / DAY, "day"),
+        s if s < 365 * DAY => (s / (30 * DAY), "month"),
+        s => (s / (365 * DAY), "year"),
+    };
+    if amount == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", amount, unit)
     }
+}
 
-    // 3. Check if upstream is set
-    let upstream_output = Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("rev-parse")
-        .arg("--abbrev-ref")
-        .arg("--symbolic-full-name")
-        .arg("@{u}")
-        .output()?;
-    if !upstream_output.status.success() {
-        return Ok(Some(RepoStatus::NoUpstream));
+/// Formats a number of bytes with a binary unit, e.g. "1.5 MiB".
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
     }
+}
 
-    // 4. Check for unpushed commits
-    let cherry_output = Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("cherry")
-        .arg("-v")
-        .output()?;
-    if !cherry_output.stdout.is_empty() {
-        return Ok(Some(RepoStatus::NotPushed));
+/// Prints `header` and `rows` to stdout as a table, with every column as wide as its widest cell.
+pub fn print_table<const N: usize>(header: [String; N], rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in std::iter::once(&header).chain(rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
     }
 
-    Ok(Some(RepoStatus::Ok))
+    for row in std::iter::once(&header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
 }
 
-/// Prints the final summary report to the console.
-fn print_report(
-    uncommitted: &[PathBuf],
-    no_upstream: &[PathBuf],
-    not_pushed: &[PathBuf],
-) -> Result<()> {
-    if !uncommitted.is_empty() {
-        logger::info("The following directories contain uncommitted changes:");
-        for dir in uncommitted {
-            println!("{}", dir.display().to_string());
+/// Checks the status of a single repository, fetching first if asked to.
+fn check_repository(args: &RemoteOperationArgs, backend: &dyn GitBackend, path: &Path) -> RepoRecord {
+    logger::debug(&format!("Checking '{}'", path.display()));
+
+    let mut record = RepoRecord {
+        path: path.to_path_buf(),
+        conditions: Vec::new(),
+        status: None,
+        fetch_error: None,
+        action: None,
+        error: None,
+    };
+
+    // Refresh the remote-tracking refs first so "behind" counts are accurate. A failed fetch
+    // is not fatal: the status is still computed against the refs we already have.
+    if args.fetch {
+        logger::debug(&format!("Fetching in '{}'...", path.display()));
+        let timeout = Duration::from_secs(args.fetch_timeout);
+        if let Err(err) = backend.fetch(path, args.prune, timeout) {
+            logger::warning(&format!("Failed to fetch in '{}': {:#}", path.display(), err));
+            record.fetch_error = Some(format!("{:#}", err));
         }
     }
 
-    if !no_upstream.is_empty() {
-        logger::info("The following directories do not have an upstream branch set:");
-        for dir in no_upstream {
-            println!("{}", dir.display().to_string());
-            let branch_output = Command::new("git")
-                .arg("-C")
-                .arg(dir)
-                .arg("rev-parse")
-                .arg("--abbrev-ref")
-                .arg("HEAD")
-                .output()?;
-            let branch = String::from_utf8(branch_output.stdout)?.trim().to_string();
-            logger::info(
-                &format!(
-                    "Remote branch 'origin/{}' exists. To link it, run: git -C {} branch --set-upstream-to=origin/{} {}",
-                    branch,
-                    dir.display(),
-                    branch,
-                    branch
-                ));
+    match backend.status(path) {
+        Ok(Some(status)) => {
+            record.conditions = status.conditions();
+            record.status = Some(status);
+        }
+        Ok(None) => {
+            logger::warning(&format!("Not a git repository: '{}'", path.display()));
+            record.error = Some("not a git repository".to_string());
+        }
+        Err(err) => {
+            logger::error(&format!("Failed to check '{}': {:#}", path.display(), err));
+            record.error = Some(format!("{:#}", err));
         }
     }
 
-    if !not_pushed.is_empty() {
-        logger::info(
-            "The following directories contain changes that were committed but not yet pushed:",
-        );
-        for dir in not_pushed {
-            println!("{}", dir.display().to_string());
+    record
+}
+
+/// Runs the action on a single repository. Returns the report entry for the action, and the
+/// refreshed status if the action may have changed it.
+fn run_action<F>(
+    backend: &dyn GitBackend,
+    path: &Path,
+    status: &RepoStatus,
+    on_ok: &F,
+) -> (ActionResult, Option<RepoStatus>)
+where
+    F: Fn(PathBuf, &RepoStatus) -> Result<ActionResult, anyhow::Error>,
+{
+    match on_ok(path.to_path_buf(), status) {
+        Ok(result @ ActionResult::DryRun { .. }) => (result, None),
+        Ok(result) => {
+            // Refresh the status so the report reflects what the action changed
+            (result, backend.status(path).ok().flatten())
+        }
+        Err(err) => {
+            // Refresh the status of a repository left with conflicts, so the report shows them
+            let result = action_failure(&err);
+            let status = match result {
+                ActionResult::LeftConflicted { .. } => backend.status(path).ok().flatten(),
+                _ => None,
+            };
+            (result, status)
         }
     }
+}
 
-    Ok(())
+/// Turns an error returned by an action into its report entry. Actions log their own errors.
+fn action_failure(err: &anyhow::Error) -> ActionResult {
+    if let Some(intervention) = err.downcast_ref::<NeedsIntervention>() {
+        return ActionResult::NeedsIntervention {
+            reason: intervention.reason.clone(),
+        };
+    }
+    if let Some(conflicted) = err.downcast_ref::<LeftConflicted>() {
+        return ActionResult::LeftConflicted {
+            reason: conflicted.reason.clone(),
+        };
+    }
+
+    if err.is::<TimedOut>() {
+        return ActionResult::Failed {
+            kind: FailureKind::TimedOut,
+            error: format!("{:#}", err),
+            stderr: String::new(),
+        };
+    }
+
+    match err.downcast_ref::<GitFailure>() {
+        Some(failure) => ActionResult::Failed {
+            kind: failure.kind,
+            error: failure.summary(),
+            stderr: failure.stderr.clone(),
+        },
+        None => ActionResult::Failed {
+            kind: FailureKind::Other,
+            error: format!("{:#}", err),
+            stderr: String::new(),
+        },
+    }
 }
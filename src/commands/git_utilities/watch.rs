@@ -0,0 +1,214 @@
+use crate::cli::{CheckReposArgs, ReportFormat};
+use crate::commands::git_utilities::backend;
+use crate::commands::git_utilities::filters::RepoFilter;
+use crate::commands::git_utilities::report::{RepoRecord, Report};
+use crate::commands::git_utilities::status::Condition;
+use crate::commands::git_utilities::utils::collect_statuses;
+use crate::utils::logger;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long to wait for a burst of filesystem events to settle before checking again. A single
+/// `git commit` or `git fetch` touches many files in quick succession.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// The conditions of every repository at the time of a check, by path. `None` when the
+/// repository could not be checked.
+type Snapshot = BTreeMap<PathBuf, Option<BTreeSet<Condition>>>;
+
+/// Checks the repositories again and again until interrupted, printing the report after each
+/// check along with the conditions that appeared or went away since the previous one.
+pub fn run(args: &CheckReposArgs) -> Result<()> {
+    let remote = &args.remote;
+
+    // Keep stdout clean for machine-readable reports
+    if remote.format != ReportFormat::Text {
+        logger::init_stderr(true);
+    }
+
+    let backend = backend::create(remote.backend);
+    let filter = RepoFilter::new(&remote.filters)?;
+    let redraw = remote.format == ReportFormat::Text && std::io::stdout().is_terminal();
+    let mut changes = args.on_change.then(ChangeWatcher::new).transpose()?;
+    let mut previous: Option<Snapshot> = None;
+
+    loop {
+        // 1. Check every repository, keeping the previous snapshot if the check itself failed
+        let records = match collect_statuses(remote, &filter, backend.as_ref()) {
+            Ok(records) => records,
+            Err(err) => {
+                logger::error(&format!("Failed to check the repositories: {:#}", err));
+                wait(changes.as_mut(), args.interval);
+                continue;
+            }
+        };
+        let snapshot = snapshot(&records);
+
+        // 2. Print the report, then what changed since the previous check
+        if redraw {
+            print!("\x1B[2J\x1B[H");
+        }
+        if remote.format == ReportFormat::Text {
+            logger::info(&format!(
+                "Checked {} repositories at {}",
+                records.len(),
+                humantime::format_rfc3339_seconds(SystemTime::now())
+            ));
+        }
+        Report::new(records).print(remote.format)?;
+        if let (Some(previous), ReportFormat::Text) = (&previous, remote.format) {
+            print_transitions(previous, &snapshot);
+        }
+
+        // 3. Watch the repositories found since the previous check, e.g. freshly cloned ones
+        if let Some(changes) = &mut changes {
+            changes.watch_new(snapshot.keys());
+        }
+        previous = Some(snapshot);
+
+        // 4. Wait for the next check
+        wait(changes.as_mut(), args.interval);
+    }
+}
+
+fn snapshot(records: &[RepoRecord]) -> Snapshot {
+    records
+        .iter()
+        .map(|record| {
+            let conditions = record
+                .status
+                .as_ref()
+                .map(|_| record.conditions.iter().copied().collect());
+            (record.path.clone(), conditions)
+        })
+        .collect()
+}
+
+/// Prints the repositories whose conditions differ between the two snapshots. New conditions
+/// are highlighted, resolved ones are shown in green.
+fn print_transitions(previous: &Snapshot, current: &Snapshot) {
+    let mut lines = Vec::new();
+
+    for (path, conditions) in current {
+        let Some(before) = previous.get(path) else {
+            lines.push(format!("{} ({})", path.display(), "new repository".cyan()));
+            continue;
+        };
+
+        let changes = match (before, conditions) {
+            (Some(before), Some(after)) => {
+                let appeared = after.difference(before).map(|c| format!("+{}", c.label()).yellow());
+                let resolved = before.difference(after).map(|c| format!("-{}", c.label()).green());
+                appeared.chain(resolved).map(|change| change.to_string()).collect()
+            }
+            (Some(_), None) => vec!["could not be checked".red().to_string()],
+            (None, Some(_)) => vec!["checked again".green().to_string()],
+            (None, None) => Vec::new(),
+        };
+        if !changes.is_empty() {
+            lines.push(format!("{} ({})", path.display(), changes.join(", ")));
+        }
+    }
+
+    for path in previous.keys().filter(|path| !current.contains_key(*path)) {
+        lines.push(format!("{} ({})", path.display(), "no longer found".red()));
+    }
+
+    if !lines.is_empty() {
+        logger::info("Changes since the previous check:");
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Sleeps for `interval`, or until something changes in a watched repository.
+fn wait(changes: Option<&mut ChangeWatcher>, interval: Duration) {
+    match changes {
+        Some(changes) => changes.wait(interval),
+        None => std::thread::sleep(interval),
+    }
+}
+
+/// Watches the `.git` directories of the repositories for changes made by git, e.g. a commit,
+/// a checkout or a fetch.
+struct ChangeWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<()>,
+    watched: HashSet<PathBuf>,
+}
+
+impl ChangeWatcher {
+    fn new() -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            // Lock files come and go on every git command, even a read-only `git status`
+            let relevant = !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|path| path.extension().is_none_or(|ext| ext != "lock"));
+            if relevant {
+                let _ = sender.send(());
+            }
+        })
+        .context("Failed to start watching for changes")?;
+
+        Ok(Self {
+            watcher,
+            events,
+            watched: HashSet::new(),
+        })
+    }
+
+    fn watch_new<'a>(&mut self, repositories: impl Iterator<Item = &'a PathBuf>) {
+        for repository in repositories {
+            if self.watched.contains(repository) {
+                continue;
+            }
+            if let Err(err) = self.watch(repository) {
+                logger::warning(&format!("Failed to watch '{}' for changes: {:#}", repository.display(), err));
+            }
+            self.watched.insert(repository.clone());
+        }
+    }
+
+    /// Watches the files of `.git` that change when the state of the repository does: `HEAD`,
+    /// the index and the refs. Objects are left out, there are far too many of them.
+    fn watch(&mut self, repository: &Path) -> Result<()> {
+        let git_dir = repository.join(".git");
+        // Worktrees and submodules have a `.git` file pointing elsewhere, they are only
+        // checked on the interval
+        if !git_dir.is_dir() {
+            return Ok(());
+        }
+
+        self.watcher.watch(&git_dir, RecursiveMode::NonRecursive)?;
+        let refs = git_dir.join("refs");
+        if refs.is_dir() {
+            self.watcher.watch(&refs, RecursiveMode::Recursive)?;
+        }
+        Ok(())
+    }
+
+    fn wait(&mut self, interval: Duration) {
+        // Events caused by the check that just ran, e.g. git refreshing the index
+        while self.events.try_recv().is_ok() {}
+
+        let deadline = Instant::now() + interval;
+        match self.events.recv_timeout(interval) {
+            Ok(()) => {
+                // Let the burst settle, without going past the interval
+                while Instant::now() < deadline && self.events.recv_timeout(DEBOUNCE).is_ok() {}
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => std::thread::sleep(deadline.saturating_duration_since(Instant::now())),
+        }
+    }
+}
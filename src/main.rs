@@ -10,6 +10,7 @@ fn main() -> Result<()> {
     let cli = Cli::parse();
 
     utils::logger::init_verbose(cli.verbose);
+    commands::git_utilities::process::init_non_interactive(cli.non_interactive);
 
     // Match the subcommand and call its run function
     match &cli.command {
@@ -25,6 +26,24 @@ fn main() -> Result<()> {
         Commands::PushRepos(args) => {
             commands::git_utilities::push_repos::run(args)?;
         }
+        Commands::CommitRepos(args) => {
+            commands::git_utilities::commit_repos::run(args)?;
+        }
+        Commands::StashRepos(args) => {
+            commands::git_utilities::stash_repos::run(args)?;
+        }
+        Commands::Branch(args) => {
+            commands::git_utilities::branch::run(args)?;
+        }
+        Commands::Exec(args) => {
+            commands::git_utilities::exec::run(args)?;
+        }
+        Commands::Sync(args) => {
+            commands::git_utilities::sync::run(args)?;
+        }
+        Commands::RepoStats(args) => {
+            commands::git_utilities::repo_stats::run(args)?;
+        }
         Commands::BulkRename(args) => {
             commands::bulk_rename::run(args)?;
         }
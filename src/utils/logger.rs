@@ -1,26 +1,90 @@
 use colored::Colorize;
+use std::cell::RefCell;
+use std::io::Write;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 static VERBOSE_ENABLED: AtomicBool = AtomicBool::new(false);
+static STDERR_ENABLED: AtomicBool = AtomicBool::new(false);
+static SILENCED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// When set, log lines emitted on this thread are appended here instead of being printed.
+    static CAPTURE_BUFFER: RefCell<Option<String>> = const { RefCell::new(None) };
+}
 
 pub fn init_verbose(enabled: bool) {
     VERBOSE_ENABLED.store(enabled, Ordering::Relaxed);
 }
 
+/// Sends log lines to stderr instead of stdout, e.g. when stdout carries a machine-readable report.
+pub fn init_stderr(enabled: bool) {
+    STDERR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Drops every log line, e.g. while a full-screen interface owns the terminal.
+#[cfg(feature = "tui")]
+pub fn silence(enabled: bool) {
+    SILENCED.store(enabled, Ordering::Relaxed);
+}
+
 pub fn info(message: &str) {
-    println!("{}: {}", "INFO".cyan(), message);
+    emit(format!("{}: {}", "INFO".cyan(), message));
 }
 
 pub fn warning(message: &str) {
-    println!("{}: {}", "WARNING".yellow(), message);
+    emit(format!("{}: {}", "WARNING".yellow(), message));
 }
 
 pub fn error(message: &str) {
-    println!("{}: {}", "ERROR".red(), message);
+    emit(format!("{}: {}", "ERROR".red(), message));
 }
 
 pub fn debug(message: &str) {
     if VERBOSE_ENABLED.load(Ordering::Relaxed) {
-        println!("{}: {}", "DEBUG".truecolor(135, 255, 135), message);
+        emit(format!("{}: {}", "DEBUG".truecolor(135, 255, 135), message));
+    }
+}
+
+/// Logs output produced by something else, e.g. an external command, as is.
+pub fn output(message: &str) {
+    emit(message.trim_end().to_string());
+}
+
+/// Runs `f` while buffering every log line it emits on the current thread.
+/// Returns the result of `f` together with the buffered output, so callers running work
+/// concurrently can print each unit's logs as one block.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, String) {
+    let previous = CAPTURE_BUFFER.with(|buffer| buffer.borrow_mut().replace(String::new()));
+    let result = f();
+    let output = CAPTURE_BUFFER.with(|buffer| std::mem::replace(&mut *buffer.borrow_mut(), previous));
+    (result, output.unwrap_or_default())
+}
+
+/// Prints a block of previously captured output without interleaving it with other threads.
+pub fn flush(output: &str) {
+    if output.is_empty() || SILENCED.load(Ordering::Relaxed) {
+        return;
+    }
+    if STDERR_ENABLED.load(Ordering::Relaxed) {
+        let mut stderr = std::io::stderr().lock();
+        let _ = stderr.write_all(output.as_bytes());
+    } else {
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(output.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+fn emit(line: String) {
+    if SILENCED.load(Ordering::Relaxed) {
+        return;
     }
+    CAPTURE_BUFFER.with(|buffer| match buffer.borrow_mut().as_mut() {
+        Some(buffer) => {
+            buffer.push_str(&line);
+            buffer.push('\n');
+        }
+        None if STDERR_ENABLED.load(Ordering::Relaxed) => eprintln!("{}", line),
+        None => println!("{}", line),
+    });
 }
@@ -1 +1,2 @@
-pub mod logger;
\ No newline at end of file
+pub mod logger;
+pub mod prompt;
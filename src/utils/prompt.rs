@@ -0,0 +1,21 @@
+use anyhow::{bail, Context, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Asks the user a yes/no question on the terminal. Anything but "y" or "yes" is a no.
+/// Fails when stdin is not a terminal, since nobody could answer.
+pub fn confirm(question: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        bail!("Cannot ask for confirmation without a terminal, pass --yes to proceed");
+    }
+
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("Failed to read the answer")?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}